@@ -2,4 +2,11 @@
 pub enum AppType {
     Species,
     Location,
+    Diff,
+    Report,
+    Where,
+    Doctor,
+    Watch,
+    Offline,
+    BarChart,
 }
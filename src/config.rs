@@ -0,0 +1,408 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+
+use toml::{Table, Value};
+
+use crate::error::MagpieError;
+
+static CONFIG_ENV: &str = "MAGPIE_CONFIG";
+static DEFAULT_CONFIG: &str = "magpie.toml";
+static ENV_PREFIX: &str = "MAGPIE_";
+static PROFILE_ENV: &str = "MAGPIE_PROFILE";
+static PROFILE_TABLE: &str = "profile";
+static SCRAPE_COMMANDS: &[&str] = &["targets", "watch"];
+/// Global options that take a value, which can come before the command.
+static GLOBAL_VALUE_FLAGS: &[&str] = &["--log_file", "--selectors", "--log_format"];
+
+/// How a setting's value is turned into `targets` flags.
+///
+/// - Value: The value is passed to the flag, e.g. `output = "out.csv"` becomes `--output out.csv`.
+/// - Choice: The value names one flag of a group, e.g. `list_type = "global"` becomes `--global`.
+/// - Months: `year`, `all`, a month, or a range of months such as `6-8`.
+/// - Format: `long` is the default output, `wide` becomes `--wide`.
+/// - Switch: `true` turns the flag on.
+enum Kind {
+    Value(&'static str),
+    Choice(&'static [&'static str]),
+    Months,
+    Format,
+    Switch(&'static str),
+}
+
+/// A run parameter that can be set in a profile or an environment variable. Settings sharing a
+/// group of flags are alternatives, and a group is only filled in if none of its flags are on the
+/// command line.
+struct Setting {
+    key: &'static str,
+    flags: &'static [&'static str],
+    kind: Kind,
+}
+
+static INPUT_FLAGS: &[&str] = &["--hotspot", "--subregion", "--codes"];
+
+static SETTINGS: &[Setting] = &[
+    Setting {
+        key: "hotspot",
+        flags: INPUT_FLAGS,
+        kind: Kind::Value("--hotspot"),
+    },
+    Setting {
+        key: "subregion",
+        flags: INPUT_FLAGS,
+        kind: Kind::Value("--subregion"),
+    },
+    Setting {
+        key: "codes",
+        flags: INPUT_FLAGS,
+        kind: Kind::Value("--codes"),
+    },
+    Setting {
+        key: "region_file",
+        flags: &["--region_file"],
+        kind: Kind::Value("--region_file"),
+    },
+    Setting {
+        key: "hotspot_file",
+        flags: &["--hotspot_file"],
+        kind: Kind::Value("--hotspot_file"),
+    },
+    Setting {
+        key: "list_type",
+        flags: &["--local", "--region", "--country", "--global"],
+        kind: Kind::Choice(&["local", "region", "country", "global"]),
+    },
+    Setting {
+        key: "date_range",
+        flags: &["--life", "--ytd", "--current_month", "--date"],
+        kind: Kind::Choice(&["life", "ytd", "current_month", "date"]),
+    },
+    Setting {
+        key: "months",
+        flags: &["--year", "--month", "--all", "--range"],
+        kind: Kind::Months,
+    },
+    Setting {
+        key: "output",
+        flags: &["--output"],
+        kind: Kind::Value("--output"),
+    },
+    Setting {
+        key: "rate_limit",
+        flags: &["--rate_limit"],
+        kind: Kind::Value("--rate_limit"),
+    },
+    Setting {
+        key: "concurrency",
+        flags: &["--concurrency"],
+        kind: Kind::Value("--concurrency"),
+    },
+    Setting {
+        key: "notify_webhook",
+        flags: &["--notify_webhook"],
+        kind: Kind::Value("--notify_webhook"),
+    },
+    Setting {
+        key: "notify_smtp",
+        flags: &["--notify_smtp"],
+        kind: Kind::Value("--notify_smtp"),
+    },
+    Setting {
+        key: "notify_to",
+        flags: &["--notify_to"],
+        kind: Kind::Value("--notify_to"),
+    },
+    Setting {
+        key: "notify_from",
+        flags: &["--notify_from"],
+        kind: Kind::Value("--notify_from"),
+    },
+    Setting {
+        key: "notify_threshold",
+        flags: &["--notify_threshold"],
+        kind: Kind::Value("--notify_threshold"),
+    },
+    Setting {
+        key: "format",
+        flags: &["--wide"],
+        kind: Kind::Format,
+    },
+    Setting {
+        key: "confidence",
+        flags: &["--confidence"],
+        kind: Kind::Switch("--confidence"),
+    },
+];
+
+impl Kind {
+    /// Translates a setting's value into command line arguments.
+    fn to_args(&self, value: &str) -> Result<Vec<String>, String> {
+        match self {
+            Kind::Value(flag) => Ok(vec![flag.to_string(), value.to_string()]),
+            Kind::Choice(choices) => {
+                let choice = value.replace('-', "_");
+                if choices.contains(&choice.as_str()) {
+                    Ok(vec![format!("--{}", choice)])
+                } else {
+                    Err(format!("expected one of {}", choices.join(", ")))
+                }
+            }
+            Kind::Months => match value {
+                "year" | "all" => Ok(vec![format!("--{}", value)]),
+                range if range.contains('-') => {
+                    Ok(vec!["--range".to_string(), range.to_string()])
+                }
+                month => Ok(vec!["--month".to_string(), month.to_string()]),
+            },
+            Kind::Format => match value {
+                "long" => Ok(vec![]),
+                "wide" => Ok(vec!["--wide".to_string()]),
+                _ => Err("expected long or wide".to_string()),
+            },
+            Kind::Switch(flag) => match value {
+                "true" => Ok(vec![flag.to_string()]),
+                "false" => Ok(vec![]),
+                _ => Err("expected true or false".to_string()),
+            },
+        }
+    }
+}
+
+/// Formats a profile value as it would be written on the command line. Lists become comma separated.
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(value_to_string)
+            .collect::<Vec<_>>()
+            .join(","),
+        other => other.to_string(),
+    }
+}
+
+/// Returns the value of a `--flag value` or `--flag=value` argument.
+fn flag_value(args: &[String], flag: &str) -> Option<String> {
+    let prefix = format!("{}=", flag);
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == flag {
+            args.get(i + 1).cloned()
+        } else {
+            arg.strip_prefix(&prefix).map(ToString::to_string)
+        }
+    })
+}
+
+/// Returns the command, the first argument after the program name that is not a global option or
+/// the value of one.
+fn command(args: &[String]) -> Option<&str> {
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if GLOBAL_VALUE_FLAGS.contains(&arg.as_str()) {
+            args.next();
+        } else if !arg.starts_with('-') {
+            return Some(arg);
+        }
+    }
+    None
+}
+
+/// Returns true if any of the flags is on the command line.
+fn has_flag(args: &[String], flags: &[&str]) -> bool {
+    args.iter()
+        .any(|arg| flags.contains(&arg.split('=').next().unwrap_or(arg)))
+}
+
+/// Loads a named profile from the config file.
+fn load_profile(config_file: &str, name: &str) -> Result<Table, MagpieError> {
+    let load_error = |message: String| MagpieError::LoadFile {
+        file: config_file.to_string(),
+        message,
+    };
+    let mut config: Table = fs::read_to_string(config_file)
+        .map_err(|e| load_error(e.to_string()))?
+        .parse()
+        .map_err(|e: toml::de::Error| load_error(e.message().to_string()))?;
+    let profile = match config.remove(PROFILE_TABLE) {
+        Some(Value::Table(mut profiles)) => profiles.remove(name),
+        _ => None,
+    };
+    match profile {
+        Some(Value::Table(profile)) => match profile
+            .keys()
+            .find(|k| !SETTINGS.iter().any(|s| s.key == k.as_str()))
+        {
+            Some(key) => Err(MagpieError::InvalidArgument(format!(
+                "Unknown setting {} in profile {}.",
+                key, name
+            ))),
+            None => Ok(profile),
+        },
+        _ => Err(MagpieError::InvalidArgument(format!(
+            "No profile {} in {}.",
+            name, config_file
+        ))),
+    }
+}
+
+/// Fills in the arguments of a `targets` or `watch` command from environment variables and a profile.
+///
+/// Each group of flags is resolved in order of precedence:
+/// 1. Flags on the command line.
+/// 2. Environment variables, named `MAGPIE_` followed by the setting in upper case, e.g. `MAGPIE_OUTPUT`.
+/// 3. The profile selected with `--profile` or `MAGPIE_PROFILE`, read from `--config`,
+///    `MAGPIE_CONFIG`, or `magpie.toml`.
+///
+/// Profiles are tables under `[profile]`, e.g. `[profile.norway-summer]`. The resolved settings are
+/// appended to the command line, so they are validated exactly like flags typed by the user.
+pub(crate) fn apply_profile<I>(args: I) -> Result<Vec<String>, MagpieError>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let mut args: Vec<String> = args
+        .into_iter()
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect();
+    if !command(&args).is_some_and(|command| SCRAPE_COMMANDS.contains(&command)) {
+        return Ok(args);
+    }
+
+    let profile = match flag_value(&args, "--profile").or_else(|| env::var(PROFILE_ENV).ok()) {
+        Some(name) => {
+            let config_file = flag_value(&args, "--config")
+                .or_else(|| env::var(CONFIG_ENV).ok())
+                .unwrap_or_else(|| DEFAULT_CONFIG.to_string());
+            load_profile(&config_file, &name)?
+        }
+        None => Table::new(),
+    };
+
+    let mut extra_args = vec![];
+    let mut groups: Vec<&[&str]> = SETTINGS.iter().map(|s| s.flags).collect();
+    groups.dedup();
+    for flags in groups {
+        if has_flag(&args, flags) {
+            continue;
+        }
+        let group: Vec<_> = SETTINGS.iter().filter(|s| s.flags == flags).collect();
+        let env_value = group.iter().find_map(|s| {
+            env::var(format!("{}{}", ENV_PREFIX, s.key.to_uppercase()))
+                .ok()
+                .map(|v| (s, v))
+        });
+        let profile_value = || {
+            group
+                .iter()
+                .find_map(|s| profile.get(s.key).map(|v| (s, value_to_string(v))))
+        };
+        if let Some((setting, value)) = env_value.or_else(profile_value) {
+            let setting_args = setting.kind.to_args(&value).map_err(|e| {
+                MagpieError::InvalidArgument(format!(
+                    "Invalid {} setting {}: {}.",
+                    setting.key, value, e
+                ))
+            })?;
+            extra_args.extend(setting_args);
+        }
+    }
+    args.extend(extra_args);
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn to_args_translates_each_kind() {
+        assert_eq!(
+            Kind::Value("--output").to_args("out.csv"),
+            Ok(vec!["--output".to_string(), "out.csv".to_string()])
+        );
+        assert_eq!(
+            Kind::Choice(&["life", "current_month"]).to_args("current-month"),
+            Ok(vec!["--current_month".to_string()])
+        );
+        assert!(Kind::Choice(&["life"]).to_args("ytd").is_err());
+        assert_eq!(Kind::Months.to_args("year"), Ok(vec!["--year".to_string()]));
+        assert_eq!(
+            Kind::Months.to_args("6-8"),
+            Ok(vec!["--range".to_string(), "6-8".to_string()])
+        );
+        assert_eq!(
+            Kind::Months.to_args("5"),
+            Ok(vec!["--month".to_string(), "5".to_string()])
+        );
+        assert_eq!(Kind::Format.to_args("long"), Ok(vec![]));
+        assert!(Kind::Format.to_args("tall").is_err());
+        assert_eq!(Kind::Switch("--confidence").to_args("false"), Ok(vec![]));
+    }
+
+    /// Environment variables are shared by every test, so all precedence checks run in this test.
+    #[test]
+    fn apply_profile_prefers_flags_then_environment_then_profile() {
+        let config_file = env::temp_dir().join(format!("magpie-{}.toml", std::process::id()));
+        fs::write(
+            &config_file,
+            "[profile.trip]\noutput = \"profile.csv\"\nlist_type = \"global\"\nmonths = \"6-8\"\n",
+        )
+        .expect("Failed to write config");
+        let config = config_file.to_string_lossy().into_owned();
+        env::set_var("MAGPIE_OUTPUT", "env.csv");
+
+        let targets = |flags: &[&str]| {
+            let mut targets_args = vec!["magpie", "targets", "--config", &config];
+            targets_args.extend(flags);
+            apply_profile(args(&targets_args))
+        };
+
+        let from_env = targets(&["--profile", "trip"]);
+        let from_flags = targets(&["--profile", "trip", "--output=flag.csv", "--local"]);
+        let unknown_profile = targets(&["--profile", "other"]);
+        let config_arg = format!("--config={}", config);
+        let after_globals = apply_profile(args(&[
+            "magpie",
+            "-v",
+            "--log_format",
+            "json",
+            "targets",
+            &config_arg,
+            "--profile=trip",
+        ]));
+        let other_command = apply_profile(args(&["magpie", "report", "--output", "x.csv"]));
+        env::remove_var("MAGPIE_OUTPUT");
+        fs::remove_file(&config_file).expect("Failed to remove config");
+
+        let from_env = from_env.expect("Failed to apply profile");
+        assert_eq!(
+            flag_value(&from_env, "--output").as_deref(),
+            Some("env.csv")
+        );
+        assert!(from_env.contains(&"--global".to_string()));
+        assert_eq!(flag_value(&from_env, "--range").as_deref(), Some("6-8"));
+
+        let from_flags = from_flags.expect("Failed to apply profile");
+        assert_eq!(
+            flag_value(&from_flags, "--output").as_deref(),
+            Some("flag.csv")
+        );
+        assert!(!from_flags.contains(&"--global".to_string()));
+
+        let after_globals = after_globals.expect("Failed to apply profile");
+        assert_eq!(
+            flag_value(&after_globals, "--output").as_deref(),
+            Some("env.csv")
+        );
+        assert!(after_globals.contains(&"--global".to_string()));
+
+        assert_eq!(
+            other_command.expect("Failed to apply profile"),
+            vec!["magpie", "report", "--output", "x.csv"]
+        );
+        assert!(unknown_profile.is_err());
+    }
+}
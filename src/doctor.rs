@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use reqwest::blocking::Client;
+use scraper::{Html, Selector};
+use tracing::info;
+
+use crate::error::MagpieError;
+use crate::location::selectors::SELECTORS as LOCATION_SELECTORS;
+use crate::location::COUNTRIES;
+use crate::login::{self, LOGIN_URL};
+use crate::target::selectors::SELECTORS as TARGET_SELECTORS;
+
+/// A page known to have content for every selector it is checked against. Selectors that only
+/// apply to another kind of page are skipped.
+struct KnownPage {
+    name: &'static str,
+    url: &'static str,
+    selectors: fn() -> &'static HashMap<&'static str, Selector>,
+    skip: &'static [&'static str],
+}
+
+/// New York County and its Central Park hotspot have far more species than any one birder has seen,
+/// so their world life list targets pages always have a table of targets.
+static REGION_TARGETS: KnownPage = KnownPage {
+    name: "region targets page",
+    url: "https://ebird.org/targets?r1=US-NY-061&r2=world&bmo=1&emo=12&t2=life",
+    selectors: || &TARGET_SELECTORS,
+    skip: &["hotspot_select"],
+};
+
+static HOTSPOT_TARGETS: KnownPage = KnownPage {
+    name: "hotspot targets page",
+    url: "https://ebird.org/targets?r1=L191106&r2=world&bmo=1&emo=12&t2=life",
+    selectors: || &TARGET_SELECTORS,
+    skip: &["region_select"],
+};
+
+static REGION_LIST: KnownPage = KnownPage {
+    name: "region list page",
+    url: COUNTRIES,
+    selectors: || &LOCATION_SELECTORS,
+    skip: &[],
+};
+
+impl KnownPage {
+    /// Fetches the page and returns each selector it is checked against, with whether it matched.
+    fn check(&self, client: &Client) -> Result<Vec<(&'static str, bool)>, Box<dyn Error>> {
+        let response = client.get(self.url).send()?;
+        if response.url().as_str().starts_with(LOGIN_URL) {
+            return Err(format!("The {} redirected to the login page.", self.name).into());
+        }
+        let doc = Html::parse_document(&response.text()?);
+        let mut results: Vec<_> = (self.selectors)()
+            .iter()
+            .filter(|(name, _)| !self.skip.contains(name))
+            .map(|(&name, selector)| (name, doc.select(selector).next().is_some()))
+            .collect();
+        results.sort();
+        Ok(results)
+    }
+
+    /// Fetches the page and fails with the names of any selectors that did not match.
+    fn verify(&self, client: &Client) -> Result<(), Box<dyn Error>> {
+        let broken: Vec<_> = self
+            .check(client)?
+            .into_iter()
+            .filter(|(_, ok)| !ok)
+            .map(|(name, _)| name.to_string())
+            .collect();
+        if broken.is_empty() {
+            info!("Selectors match the {}", self.name);
+            Ok(())
+        } else {
+            Err(MagpieError::SelectorDrift {
+                page: self.name.to_string(),
+                selectors: broken,
+            }
+            .into())
+        }
+    }
+}
+
+/// Checks the selectors used to scrape targets against known pages before a run, so that changes
+/// to eBird's markup fail fast instead of producing empty output. Requires a logged in client.
+pub(crate) fn check_targets(client: &Client) -> Result<(), Box<dyn Error>> {
+    REGION_TARGETS.verify(client)?;
+    HOTSPOT_TARGETS.verify(client)
+}
+
+/// Checks the selectors used to scrape regions and hotspots against a known page.
+pub(crate) fn check_locations(client: &Client) -> Result<(), Box<dyn Error>> {
+    REGION_LIST.verify(client)
+}
+
+/// Checks every selector against known pages and prints whether each one matched.
+pub(crate) fn run() -> Result<(), Box<dyn Error>> {
+    let client = login::login()?.client;
+    let mut broken = vec![];
+    for page in [&REGION_TARGETS, &HOTSPOT_TARGETS, &REGION_LIST] {
+        println!("{}: {}", page.name, page.url);
+        for (name, ok) in page.check(&client)? {
+            println!("  {:<16} {}", name, if ok { "ok" } else { "BROKEN" });
+            if !ok {
+                broken.push(format!("{} ({})", name, page.name));
+            }
+        }
+    }
+    if broken.is_empty() {
+        Ok(())
+    } else {
+        Err(MagpieError::SelectorDrift {
+            page: "known pages".to_string(),
+            selectors: broken,
+        }
+        .into())
+    }
+}
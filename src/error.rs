@@ -0,0 +1,58 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Exit code for invalid command line input, matching the exit code clap uses for usage errors.
+static USAGE_EXIT_CODE: u8 = 2;
+
+/// Exit code for failures while running a command.
+static FAILURE_EXIT_CODE: u8 = 1;
+
+/// Errors reported to the user instead of panicking.
+///
+/// - InvalidArgument: The command line is valid for clap but not for the locations being scraped,
+///   e.g. a region list type for hotspot codes.
+/// - LoadFile: An input or output file could not be read.
+/// - SelectorDrift: A page no longer matches the selectors used to scrape it, usually because eBird
+///   changed its markup.
+#[derive(Debug)]
+pub(crate) enum MagpieError {
+    InvalidArgument(String),
+    LoadFile { file: String, message: String },
+    SelectorDrift { page: String, selectors: Vec<String> },
+}
+
+impl MagpieError {
+    /// Returns the process exit code for the error.
+    pub(crate) fn exit_code(&self) -> u8 {
+        match self {
+            MagpieError::InvalidArgument(_) => USAGE_EXIT_CODE,
+            MagpieError::LoadFile { .. } | MagpieError::SelectorDrift { .. } => FAILURE_EXIT_CODE,
+        }
+    }
+
+    /// Returns the process exit code for any error returned by a command.
+    pub(crate) fn exit_code_for(error: &(dyn Error + 'static)) -> u8 {
+        error
+            .downcast_ref::<MagpieError>()
+            .map_or(FAILURE_EXIT_CODE, MagpieError::exit_code)
+    }
+}
+
+impl Display for MagpieError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MagpieError::InvalidArgument(message) => write!(f, "{}", message),
+            MagpieError::LoadFile { file, message } => {
+                write!(f, "Failed to load {}: {}", file, message)
+            }
+            MagpieError::SelectorDrift { page, selectors } => write!(
+                f,
+                "Selectors {} no longer match the {}. eBird may have changed its pages.",
+                selectors.join(", "),
+                page
+            ),
+        }
+    }
+}
+
+impl Error for MagpieError {}
@@ -1,13 +1,119 @@
-use polars::prelude::{DataFrame, LazyCsvReader, LazyFileListReader};
+use std::collections::HashSet;
+use std::error::Error;
+
+use polars::prelude::{BooleanChunked, DataFrame, LazyCsvReader, LazyFileListReader};
+
+use crate::error::MagpieError;
+use crate::target::scrape_params::LocationLevel;
+
+static HOTSPOT_CODE: &str = "hotspot_code";
+static SUB_REGION_CODE: &str = "sub_region_code";
 
 /**
 Loads the csv consisting of all locations for which data is to be scraped.
 */
-
-pub(super) fn load_data(loc_file: &str) -> DataFrame {
+pub(super) fn load_data(loc_file: &str) -> Result<DataFrame, MagpieError> {
     LazyCsvReader::new(loc_file)
         .with_has_header(true)
         .finish()
         .and_then(|f| f.collect())
-        .unwrap_or_else(|e| panic!("Failed to load {}: {:?}", loc_file, e))
+        .map_err(|e| MagpieError::LoadFile {
+            file: loc_file.to_string(),
+            message: e.to_string(),
+        })
+}
+
+/**
+Checks that a table loaded from `loc_file` has every column in `columns`.
+Returns an error naming the columns that are missing.
+*/
+pub(super) fn check_columns(
+    loc_df: &DataFrame,
+    loc_file: &str,
+    columns: &[String],
+) -> Result<(), MagpieError> {
+    let missing: Vec<_> = columns
+        .iter()
+        .filter(|c| loc_df.get_column_index(c).is_none())
+        .map(|c| c.as_str())
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(MagpieError::LoadFile {
+            file: loc_file.to_string(),
+            message: format!("missing columns {}", missing.join(", ")),
+        })
+    }
+}
+
+/**
+Hotspot codes are an `L` followed by digits. Every other code is treated as a sub-region code.
+*/
+fn is_hotspot_code(code: &str) -> bool {
+    code.strip_prefix('L')
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/**
+Selects the rows of a reference table whose `code_column` is one of `codes`.
+Returns an error naming any codes that are not in the table.
+*/
+fn select_codes(
+    ref_file: &str,
+    code_column: &str,
+    codes: &HashSet<&str>,
+) -> Result<DataFrame, Box<dyn Error>> {
+    let ref_df = load_data(ref_file)?;
+    check_columns(&ref_df, ref_file, &[code_column.to_string()])?;
+    let code_series = ref_df.column(code_column)?.str()?;
+    let mask: BooleanChunked = code_series
+        .into_iter()
+        .map(|c| c.is_some_and(|c| codes.contains(c)))
+        .collect();
+    let loc_df = ref_df.filter(&mask)?;
+
+    let found: HashSet<&str> = loc_df.column(code_column)?.str()?.into_no_null_iter().collect();
+    let mut missing: Vec<_> = codes.difference(&found).copied().collect();
+    if missing.is_empty() {
+        Ok(loc_df)
+    } else {
+        missing.sort_unstable();
+        Err(MagpieError::InvalidArgument(format!(
+            "Codes not found in {}: {}",
+            ref_file,
+            missing.join(", ")
+        ))
+        .into())
+    }
+}
+
+/**
+Builds the location tables for a list of sub-region and hotspot codes given on the command line.
+Names and parent codes are looked up in the region and hotspot reference files, so each table has
+the same layout as an input file loaded with `load_data`. One table is returned per location level
+present in the codes.
+*/
+pub(super) fn load_codes(
+    codes: &[&str],
+    region_file: &str,
+    hotspot_file: &str,
+) -> Result<Vec<(LocationLevel, DataFrame)>, Box<dyn Error>> {
+    let (hotspot_codes, sub_region_codes): (HashSet<&str>, HashSet<&str>) =
+        codes.iter().copied().partition(|c| is_hotspot_code(c));
+
+    let mut loc_data = vec![];
+    if !sub_region_codes.is_empty() {
+        loc_data.push((
+            LocationLevel::SubRegion,
+            select_codes(region_file, SUB_REGION_CODE, &sub_region_codes)?,
+        ));
+    }
+    if !hotspot_codes.is_empty() {
+        loc_data.push((
+            LocationLevel::Hotspot,
+            select_codes(hotspot_file, HOTSPOT_CODE, &hotspot_codes)?,
+        ));
+    }
+    Ok(loc_data)
 }
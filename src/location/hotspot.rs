@@ -7,6 +7,7 @@ use scraper::ElementRef;
 use std::collections::HashSet;
 use std::thread;
 use std::time::Duration;
+use tracing::warn;
 
 fn parse_hotspot<'a>(
     row: &ElementRef,
@@ -24,10 +25,10 @@ pub fn get_hotspots<'a>(
     let html = match get_html(client, &hotspot_url) {
         Ok(html) => html,
         Err(e) => {
-            eprintln!(
-                "Error fetching hotspots for {}: {}",
-                sub_region.sub_region(),
-                e
+            warn!(
+                sub_region = sub_region.sub_region(),
+                error = %e,
+                "Failed to fetch hotspots"
             );
             return vec![];
         }
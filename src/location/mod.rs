@@ -4,7 +4,7 @@ mod loc;
 pub mod regions;
 pub mod selectors;
 
-static COUNTRIES: &str = "https://ebird.org/region/world/subregions";
+pub(crate) static COUNTRIES: &str = "https://ebird.org/region/world/subregions";
 
 static HOTSPOT: &str = "hotspots";
 
@@ -6,6 +6,7 @@ use scraper::{ElementRef, Html};
 use std::collections::HashSet;
 use std::thread;
 use std::time::Duration;
+use tracing::warn;
 
 pub(crate) fn get_html(client: &Client, url: &str) -> Result<Html, reqwest::Error> {
     client
@@ -22,7 +23,7 @@ pub(crate) fn parse_row(row: &ElementRef) -> Result<(String, String), &'static s
         .attr("href")
         .ok_or("No url for row")?
         .split('/')
-        .last()
+        .next_back()
         .ok_or("Improperly formatted url for row")?;
     Ok((name.to_owned(), code.to_owned()))
 }
@@ -66,7 +67,7 @@ pub fn get_regions<'a>(client: &Client, country: &'a Country, tries: u64) -> Vec
     let html = match get_html(client, &region_url) {
         Ok(html) => html,
         Err(e) => {
-            eprintln!("Error fetching regions for {}: {}", country.country(), e);
+            warn!(country = country.country(), error = %e, "Failed to fetch regions");
             return vec![];
         }
     };
@@ -102,7 +103,7 @@ pub fn get_sub_regions<'a>(client: &Client, region: &'a Region, tries: u64) -> V
     let html = match get_html(client, &sub_region_url) {
         Ok(html) => html,
         Err(e) => {
-            eprintln!("Error fetching sub-regions for {}: {}", region.region(), e);
+            warn!(region = region.region(), error = %e, "Failed to fetch sub-regions");
             return vec![];
         }
     };
@@ -1,14 +1,19 @@
+use crate::selector_file::selector_override;
 use lazy_static::lazy_static;
 use scraper::Selector;
 use std::collections::HashMap;
 
 macro_rules! define_selectors {
-    ($($name:ident => $selector:expr),+ $(,)?) => {
+    ($group:literal; $($name:ident => $selector:expr),+ $(,)?) => {
+        /// The built in selectors, which an override file can replace by name.
+        pub static DEFAULTS: &[(&str, &str)] = &[$((stringify!($name), $selector)),+];
+
         lazy_static! {
             pub static ref SELECTORS: HashMap<&'static str, Selector> = {
                 let mut m = HashMap::new();
                 $(
-                    m.insert(stringify!($name), Selector::parse($selector)
+                    let selector = selector_override($group, stringify!($name)).unwrap_or($selector);
+                    m.insert(stringify!($name), Selector::parse(selector)
                         .unwrap_or_else(|_| panic!("Failed to parse '{}' selector", stringify!($name))));
                 )+
                 m
@@ -29,6 +34,7 @@ macro_rules! define_selectors {
 }
 
 define_selectors! {
+    "location";
     a => "a",
     leaderboard => r#"div[class="LeaderBoardSection"]"#
 }
@@ -0,0 +1,104 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use lazy_static::lazy_static;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, Registry};
+
+use crate::error::MagpieError;
+
+lazy_static! {
+    static ref PROGRESS: MultiProgress = MultiProgress::new();
+}
+
+/// Format of log lines, human readable text or one JSON object per line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Writes log lines to standard error, hiding progress bars while a line is written so the two
+/// never share a line of the terminal.
+#[derive(Clone, Copy)]
+struct ProgressWriter;
+
+impl Write for ProgressWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        PROGRESS.suspend(|| io::stderr().write_all(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for ProgressWriter {
+    type Writer = ProgressWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        *self
+    }
+}
+
+/// Returns the log level for the number of `-v` and `-q` flags. The default level is info.
+fn level(verbose: u8, quiet: u8) -> LevelFilter {
+    match i16::from(verbose) - i16::from(quiet) {
+        i16::MIN..=-3 => LevelFilter::OFF,
+        -2 => LevelFilter::ERROR,
+        -1 => LevelFilter::WARN,
+        0 => LevelFilter::INFO,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+fn layer<W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    }
+}
+
+/// Sends log events to standard error and, if given, a log file.
+pub(crate) fn init(
+    verbose: u8,
+    quiet: u8,
+    log_file: Option<&str>,
+    format: LogFormat,
+) -> Result<(), MagpieError> {
+    let file_layer = log_file
+        .map(|path| {
+            File::create(path)
+                .map(|file| layer(format, Arc::new(file), false))
+                .map_err(|e| MagpieError::LoadFile {
+                    file: path.to_string(),
+                    message: e.to_string(),
+                })
+        })
+        .transpose()?;
+    let mut layers = vec![layer(format, ProgressWriter, true)];
+    layers.extend(file_layer);
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(level(verbose, quiet))
+        .init();
+    Ok(())
+}
+
+/// Creates a progress bar that log lines are written around.
+pub(crate) fn progress_bar(len: usize, style: ProgressStyle) -> ProgressBar {
+    PROGRESS.add(ProgressBar::new(len as u64).with_style(style))
+}
@@ -1,11 +1,14 @@
 use std::error::Error;
 use std::io::{self, Write};
+use std::sync::Arc;
 
 use reqwest::blocking::Client;
+use reqwest::cookie::Jar;
 use rpassword::prompt_password;
 use scraper::{Html, Selector};
+use tracing::{debug, info};
 
-const LOGIN_URL: &str = "https://secure.birds.cornell.edu/cassso/login";
+pub(crate) const LOGIN_URL: &str = "https://secure.birds.cornell.edu/cassso/login";
 const TOKEN_SELECTOR: &str = r#"input[name="lt"]"#;
 
 fn get_token(client: &Client) -> Result<String, Box<dyn Error>> {
@@ -20,25 +23,64 @@ fn get_token(client: &Client) -> Result<String, Box<dyn Error>> {
         .ok_or_else(|| Box::<dyn Error>::from("No Login Token Provided"))
 }
 
-pub(crate) fn login() -> Result<Client, Box<dyn Error>> {
-    let client = Client::builder().cookie_store(true).build()?;
-    let token = get_token(&client)?;
+/// A logged in session. The blocking client and the async clients made from it share cookies.
+pub(crate) struct Session {
+    pub(crate) client: Client,
+    jar: Arc<Jar>,
+}
+
+impl Session {
+    /// Returns an async client logged in with the session's cookies.
+    pub(crate) fn async_client(&self) -> reqwest::Result<reqwest::Client> {
+        reqwest::Client::builder()
+            .cookie_provider(self.jar.clone())
+            .build()
+    }
+}
+
+/// eBird username and password, kept in memory so a long running command can log in again.
+pub(crate) struct Credentials {
+    username: String,
+    password: String,
+}
 
-    print!("Username: ");
-    io::stdout().flush()?;
-    let mut username = String::new();
-    io::stdin().read_line(&mut username)?;
+impl Credentials {
+    /// Prompts for a username and password.
+    pub(crate) fn prompt() -> Result<Self, Box<dyn Error>> {
+        print!("Username: ");
+        io::stdout().flush()?;
+        let mut username = String::new();
+        io::stdin().read_line(&mut username)?;
 
-    let password = prompt_password("Password: ")?;
+        let password = prompt_password("Password: ")?;
+        Ok(Self {
+            username: username.trim().to_string(),
+            password,
+        })
+    }
+}
+
+/// Prompts for credentials and logs in.
+pub(crate) fn login() -> Result<Session, Box<dyn Error>> {
+    login_with(&Credentials::prompt()?)
+}
+
+/// Logs in with credentials that were already entered.
+pub(crate) fn login_with(credentials: &Credentials) -> Result<Session, Box<dyn Error>> {
+    let jar = Arc::new(Jar::default());
+    let client = Client::builder().cookie_provider(jar.clone()).build()?;
+    let token = get_token(&client)?;
+    debug!("Received login token");
 
     let login_data = [
-        ("username", username.trim()),
-        ("password", &password),
+        ("username", credentials.username.as_str()),
+        ("password", &credentials.password),
         ("lt", &token),
         ("execution", "e1s1"),
         ("_eventId", "submit"),
     ];
 
-    client.post(LOGIN_URL).form(&login_data).send()?;
-    Ok(client)
+    let response = client.post(LOGIN_URL).form(&login_data).send()?;
+    info!(status = %response.status(), "Logged in as {}", credentials.username);
+    Ok(Session { client, jar })
 }
@@ -3,23 +3,61 @@ extern crate strum;
 extern crate strum_macros;
 
 mod app;
+mod config;
+mod doctor;
+mod error;
 mod loc;
 mod location;
+mod logging;
 mod login;
+mod notify;
+mod offline;
 mod parse;
+mod report;
+mod run_barchart;
 mod run_location;
+mod run_report;
 mod run_scraper;
+mod run_watch;
+mod selector_file;
+mod sink;
+mod sqlite;
 mod target;
 
 use std::error::Error;
+use std::process::ExitCode;
 
 use crate::app::AppType;
+use crate::error::MagpieError;
 use crate::parse::MagpieParse;
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let matches = parse::parse();
-    match matches.get_app() {
-        AppType::Species => run_scraper::run(&matches),
+fn run() -> Result<(), Box<dyn Error>> {
+    let matches = parse::parse()?;
+    let (app, app_matches) = matches.get_app()?;
+    let (verbose, quiet, log_file, log_format) = app_matches.get_log_settings();
+    logging::init(verbose, quiet, log_file, log_format)?;
+    if let Some(selector_file) = app_matches.get_selector_file() {
+        selector_file::load(selector_file)?;
+    }
+    match app {
+        AppType::Species => run_scraper::run(app_matches),
         AppType::Location => run_location::run(),
+        AppType::Diff => run_report::run_diff(app_matches),
+        AppType::Report => run_report::run(app_matches),
+        AppType::Where => run_report::run_where(app_matches),
+        AppType::Doctor => doctor::run(),
+        AppType::Watch => run_watch::run(app_matches),
+        AppType::Offline => offline::run(app_matches),
+        AppType::BarChart => run_barchart::run(app_matches),
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(MagpieError::exit_code_for(e.as_ref()))
+        }
     }
 }
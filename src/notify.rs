@@ -0,0 +1,265 @@
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use chrono::Local;
+use polars::prelude::*;
+use reqwest::blocking::Client;
+use serde_json::{json, Map, Value};
+use tracing::info;
+
+use crate::target::{
+    COMMON_NAME, COUNTRY, END_MONTH, HOTSPOT, PERCENT, REGION, START_MONTH, SUB_REGION,
+};
+
+/// How long to wait for each reply from an SMTP server.
+static SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where notifications of new targets are sent.
+///
+/// - Webhook: The new targets are POSTed as JSON to the URL.
+/// - Smtp: The new targets are emailed through a plain SMTP server, given as `host:port`, without
+///   TLS or authentication, e.g. a local relay.
+pub(crate) enum Notifier {
+    Webhook {
+        url: String,
+    },
+    Smtp {
+        server: String,
+        from: String,
+        to: Vec<String>,
+    },
+}
+
+/// Notifiers to send new targets to, and the lowest frequency of a new target worth sending.
+pub(crate) struct Notifications {
+    pub(crate) notifiers: Vec<Notifier>,
+    pub(crate) threshold: f64,
+}
+
+/// Converts a table cell to a JSON value.
+fn to_json(value: AnyValue) -> Value {
+    match value {
+        AnyValue::Null => Value::Null,
+        AnyValue::Boolean(b) => json!(b),
+        AnyValue::String(s) => json!(s),
+        AnyValue::StringOwned(s) => json!(s.as_str()),
+        AnyValue::Float32(f) => json!(f),
+        AnyValue::Float64(f) => json!(f),
+        AnyValue::Int32(i) => json!(i),
+        AnyValue::Int64(i) => json!(i),
+        AnyValue::UInt32(i) => json!(i),
+        AnyValue::UInt64(i) => json!(i),
+        other => json!(other.to_string()),
+    }
+}
+
+/// Builds the JSON payload of a notification: the output file, the threshold, and one object per
+/// new target keyed by the columns of the output file.
+fn payload(output_file: &str, threshold: f64, targets: &DataFrame) -> PolarsResult<Value> {
+    let rows = (0..targets.height())
+        .map(|i| {
+            targets
+                .get_columns()
+                .iter()
+                .map(|column| Ok((column.name().to_string(), to_json(column.get(i)?))))
+                .collect::<PolarsResult<Map<_, _>>>()
+                .map(Value::Object)
+        })
+        .collect::<PolarsResult<Vec<_>>>()?;
+    Ok(json!({
+        "output": output_file,
+        "threshold": threshold,
+        "new_targets": rows,
+    }))
+}
+
+/// Describes each new target on one line, e.g.
+/// `Meadow Pipit 41.98% at Utsira, Rogaland, Rogaland, Norway in months 1-12`.
+fn summary_lines(payload: &Value) -> Vec<String> {
+    let field = |row: &Value, name: &str| match &row[name] {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    };
+    payload["new_targets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|row| {
+            let place = [HOTSPOT, SUB_REGION, REGION, COUNTRY]
+                .iter()
+                .map(|&c| field(row, c))
+                .filter(|p| !p.is_empty())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let percent = row[PERCENT].as_f64().unwrap_or_default();
+            format!(
+                "{} {:.2}% at {} in months {}-{}",
+                field(row, COMMON_NAME),
+                percent,
+                place,
+                field(row, START_MONTH),
+                field(row, END_MONTH)
+            )
+        })
+        .collect()
+}
+
+fn post_webhook(url: &str, payload: &Value) -> Result<(), Box<dyn Error>> {
+    Client::new()
+        .post(url)
+        .json(payload)
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Reads an SMTP reply, which may span several lines, and checks its code.
+fn read_reply(reader: &mut impl BufRead, expected: &[u16]) -> Result<(), Box<dyn Error>> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err("SMTP server closed the connection.".into());
+        }
+        let code = line.get(..3).and_then(|c| c.parse::<u16>().ok());
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue;
+        }
+        return match code {
+            Some(code) if expected.contains(&code) => Ok(()),
+            _ => Err(format!("Unexpected SMTP reply: {}", line.trim_end()).into()),
+        };
+    }
+}
+
+/// Sends a command to an SMTP server and checks the reply.
+fn smtp_command(
+    stream: &mut TcpStream,
+    reader: &mut impl BufRead,
+    command: &str,
+    expected: &[u16],
+) -> Result<(), Box<dyn Error>> {
+    write!(stream, "{}\r\n", command)?;
+    read_reply(reader, expected)
+}
+
+/// Sends a plain text email through an SMTP server.
+fn send_email(
+    server: &str,
+    from: &str,
+    to: &[String],
+    subject: &str,
+    body: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut stream = TcpStream::connect(server)?;
+    stream.set_read_timeout(Some(SMTP_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    read_reply(&mut reader, &[220])?;
+    smtp_command(&mut stream, &mut reader, "HELO magpie", &[250])?;
+    smtp_command(
+        &mut stream,
+        &mut reader,
+        &format!("MAIL FROM:<{}>", from),
+        &[250],
+    )?;
+    for recipient in to {
+        smtp_command(
+            &mut stream,
+            &mut reader,
+            &format!("RCPT TO:<{}>", recipient),
+            &[250, 251],
+        )?;
+    }
+    smtp_command(&mut stream, &mut reader, "DATA", &[354])?;
+
+    let headers = [
+        format!("From: {}", from),
+        format!("To: {}", to.join(", ")),
+        format!("Subject: {}", subject),
+        format!("Date: {}", Local::now().to_rfc2822()),
+        "Content-Type: text/plain; charset=utf-8".to_string(),
+    ];
+    // Lines starting with a dot are escaped with a second dot, since a lone dot ends the message.
+    let lines = body.lines().map(|line| {
+        if line.starts_with('.') {
+            format!(".{}", line)
+        } else {
+            line.to_string()
+        }
+    });
+    let message = headers
+        .into_iter()
+        .chain([String::new()])
+        .chain(lines)
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    smtp_command(
+        &mut stream,
+        &mut reader,
+        &format!("{}\r\n.", message),
+        &[250],
+    )?;
+    smtp_command(&mut stream, &mut reader, "QUIT", &[221])
+}
+
+/// Sends the new targets of a run to every notifier. Nothing is sent if there are no new targets.
+pub(crate) fn notify(
+    notifications: &Notifications,
+    output_file: &str,
+    targets: &DataFrame,
+) -> Result<(), Box<dyn Error>> {
+    if targets.height() == 0 {
+        info!(
+            "No new targets of at least {}% in {}",
+            notifications.threshold, output_file
+        );
+        return Ok(());
+    }
+    let payload = payload(output_file, notifications.threshold, targets)?;
+    for notifier in &notifications.notifiers {
+        match notifier {
+            Notifier::Webhook { url } => {
+                post_webhook(url, &payload)?;
+                info!("Sent {} new targets to {}", targets.height(), url);
+            }
+            Notifier::Smtp { server, from, to } => {
+                let subject = format!("{} new targets in {}", targets.height(), output_file);
+                send_email(
+                    server,
+                    from,
+                    to,
+                    &subject,
+                    &summary_lines(&payload).join("\n"),
+                )?;
+                info!(
+                    "Emailed {} new targets to {}",
+                    targets.height(),
+                    to.join(", ")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_reply_reads_multiline_replies() {
+        let mut reply = "250-smtp.example.com\r\n250-SIZE 1000\r\n250 OK\r\n354 Go\r\n".as_bytes();
+        assert!(read_reply(&mut reply, &[250]).is_ok());
+        assert!(read_reply(&mut reply, &[354]).is_ok());
+    }
+
+    #[test]
+    fn read_reply_rejects_unexpected_codes() {
+        assert!(read_reply(&mut "550 No such user\r\n".as_bytes(), &[250, 251]).is_err());
+        assert!(read_reply(&mut "250-Partial\r\n".as_bytes(), &[250]).is_err());
+        assert!(read_reply(&mut "OK\r\n".as_bytes(), &[250]).is_err());
+    }
+}
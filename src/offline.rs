@@ -0,0 +1,532 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+use chrono::{Datelike, Local};
+use clap::ArgMatches;
+use polars::prelude::*;
+use tracing::info;
+
+use crate::error::MagpieError;
+use crate::loc::load_data;
+use crate::parse::MagpieParse;
+use crate::report::{strings, write_table};
+use crate::target::scrape_params::{ListType, LocationLevel};
+use crate::target::{
+    COMMON_NAME, COUNTRY, END_MONTH, HOTSPOT, PERCENT, REGION, SCIENTIFIC_NAME, START_MONTH,
+    SUB_REGION,
+};
+
+static BLANK: &str = "blank";
+static EXPORT_COMMON_NAME: &str = "Common Name";
+static EXPORT_DATE: &str = "Date";
+static EXPORT_LOCATION_ID: &str = "Location ID";
+static EXPORT_REGION: &str = "State/Province";
+static EXPORT_SCIENTIFIC_NAME: &str = "Scientific Name";
+static EXPORT_SUB_REGION: &str = "County";
+static KEEP: &str = "keep";
+
+/// Names a species so that it matches across eBird's export and magpie output: by the genus and
+/// species of its scientific name, and by its common name. Subspecies and groups, e.g.
+/// `American Robin (migratorius)`, match their species.
+fn species_keys(common: Option<&str>, scientific: Option<&str>) -> Vec<String> {
+    let scientific = scientific
+        .map(|s| {
+            s.split_whitespace()
+                .take(2)
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase()
+        })
+        .filter(|s| !s.is_empty())
+        .map(|s| format!("scientific:{}", s));
+    let common = common
+        .and_then(|c| c.split(" (").next())
+        .map(|c| c.trim().to_lowercase())
+        .filter(|c| !c.is_empty())
+        .map(|c| format!("common:{}", c));
+    scientific.into_iter().chain(common).collect()
+}
+
+/// The species in a personal eBird data export, grouped by the areas a list can cover.
+/// Sub-regions are keyed by region code and county name, since the export has no sub-region codes.
+#[derive(Default)]
+struct Sightings {
+    global: HashSet<String>,
+    by_country: HashMap<String, HashSet<String>>,
+    by_region: HashMap<String, HashSet<String>>,
+    by_sub_region: HashMap<(String, String), HashSet<String>>,
+    by_hotspot: HashMap<String, HashSet<String>>,
+}
+
+/// The codes of a location in a magpie output file, looked up by name in a reference file.
+struct LocationCodes {
+    country: String,
+    region: String,
+    sub_region: String,
+    sub_region_name: String,
+    hotspot: Option<String>,
+}
+
+impl Sightings {
+    /// Groups the species of an export. If `year` is given, only sightings from that year count.
+    fn load(export: &DataFrame, year: Option<i32>) -> PolarsResult<Self> {
+        let common_names = strings(export, EXPORT_COMMON_NAME)?;
+        let scientific_names = strings(export, EXPORT_SCIENTIFIC_NAME)?;
+        let regions = strings(export, EXPORT_REGION)?;
+        let counties = strings(export, EXPORT_SUB_REGION)?;
+        let locations = strings(export, EXPORT_LOCATION_ID)?;
+        let dates = strings(export, EXPORT_DATE)?;
+        let year = year.map(|y| y.to_string());
+
+        let mut sightings = Self::default();
+        for i in 0..export.height() {
+            if year
+                .as_ref()
+                .is_some_and(|y| !dates[i].as_ref().is_some_and(|d| d.starts_with(y)))
+            {
+                continue;
+            }
+            let keys = species_keys(common_names[i].as_deref(), scientific_names[i].as_deref());
+            let region = regions[i].clone().unwrap_or_default();
+            let country = region.split('-').next().unwrap_or_default().to_string();
+
+            sightings.global.extend(keys.iter().cloned());
+            sightings
+                .by_country
+                .entry(country)
+                .or_default()
+                .extend(keys.iter().cloned());
+            if let Some(county) = &counties[i] {
+                sightings
+                    .by_sub_region
+                    .entry((region.clone(), county.clone()))
+                    .or_default()
+                    .extend(keys.iter().cloned());
+            }
+            sightings
+                .by_region
+                .entry(region)
+                .or_default()
+                .extend(keys.iter().cloned());
+            if let Some(location) = &locations[i] {
+                sightings
+                    .by_hotspot
+                    .entry(location.clone())
+                    .or_default()
+                    .extend(keys.iter().cloned());
+            }
+        }
+        Ok(sightings)
+    }
+
+    /// Returns the species on the list of the given type for a location.
+    fn seen(
+        &self,
+        list_type: &ListType,
+        codes: Option<&LocationCodes>,
+    ) -> Option<&HashSet<String>> {
+        match (list_type, codes) {
+            (ListType::Global, _) => Some(&self.global),
+            (_, None) => None,
+            (ListType::Country, Some(codes)) => self.by_country.get(&codes.country),
+            (ListType::Region, Some(codes)) => self.by_region.get(&codes.region),
+            (ListType::SubRegion, Some(codes)) if codes.sub_region == codes.region => {
+                self.by_region.get(&codes.region)
+            }
+            (ListType::SubRegion, Some(codes)) => self
+                .by_sub_region
+                .get(&(codes.region.clone(), codes.sub_region_name.clone())),
+            (ListType::Hotspot, Some(codes)) => {
+                codes.hotspot.as_ref().and_then(|h| self.by_hotspot.get(h))
+            }
+        }
+    }
+}
+
+/// Maps the location names of a reference file, e.g. `regions.csv` or `hotspots.csv`, to their codes.
+fn reference_codes(
+    reference: &DataFrame,
+    name_columns: &[&str],
+) -> PolarsResult<HashMap<Vec<Option<String>>, LocationCodes>> {
+    let names = name_columns
+        .iter()
+        .map(|&c| strings(reference, c))
+        .collect::<PolarsResult<Vec<_>>>()?;
+    let countries = strings(reference, &ListType::Country.to_string())?;
+    let regions = strings(reference, &ListType::Region.to_string())?;
+    let sub_regions = strings(reference, &ListType::SubRegion.to_string())?;
+    let hotspots = if name_columns.contains(&HOTSPOT) {
+        strings(reference, &ListType::Hotspot.to_string())?
+    } else {
+        vec![None; reference.height()]
+    };
+    Ok((0..reference.height())
+        .map(|i| {
+            let key: Vec<_> = names.iter().map(|n| n[i].clone()).collect();
+            let codes = LocationCodes {
+                country: countries[i].clone().unwrap_or_default(),
+                region: regions[i].clone().unwrap_or_default(),
+                sub_region: sub_regions[i].clone().unwrap_or_default(),
+                sub_region_name: names[2][i].clone().unwrap_or_default(),
+                hotspot: hotspots[i].clone(),
+            };
+            (key, codes)
+        })
+        .collect())
+}
+
+/// Computes targets from a magpie output file and a personal eBird data export, without logging in.
+///
+/// Each row of `frequencies` is a species at a location and month range. Rows for species already
+/// on the list of the given type, according to the export, are dropped. The output file should
+/// come from a `--local` run, which lists every species not yet seen at each location, so any wider
+/// list can be computed from it. Locations left with no targets keep a single row with no species.
+/// `reference` is the region or hotspot file used to look up the codes of each location; it is not
+/// needed for global lists.
+fn offline_targets(
+    frequencies: DataFrame,
+    sightings: &Sightings,
+    reference: Option<&DataFrame>,
+    list_type: &ListType,
+) -> Result<DataFrame, Box<dyn Error>> {
+    let mut name_columns = vec![COUNTRY, REGION, SUB_REGION];
+    if frequencies.get_column_index(HOTSPOT).is_some() {
+        name_columns.push(HOTSPOT);
+    }
+    let codes = reference
+        .map(|r| reference_codes(r, &name_columns))
+        .transpose()?;
+    let names = name_columns
+        .iter()
+        .map(|&c| strings(&frequencies, c))
+        .collect::<PolarsResult<Vec<_>>>()?;
+    let months = [START_MONTH, END_MONTH]
+        .iter()
+        .filter(|&&c| frequencies.get_column_index(c).is_some())
+        .map(|&c| strings(&frequencies, c))
+        .collect::<PolarsResult<Vec<_>>>()?;
+    let common_names = strings(&frequencies, COMMON_NAME)?;
+    let scientific_names = strings(&frequencies, SCIENTIFIC_NAME)?;
+
+    let mut keep = vec![false; frequencies.height()];
+    let mut first_rows: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+    let mut kept_locations = HashSet::new();
+    let mut missing = HashSet::new();
+    for i in 0..frequencies.height() {
+        let location: Vec<_> = names.iter().map(|n| n[i].clone()).collect();
+        let location_codes = match &codes {
+            Some(codes) => match codes.get(&location) {
+                Some(location_codes) => Some(location_codes),
+                None => {
+                    missing.insert(
+                        location
+                            .iter()
+                            .flatten()
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    );
+                    continue;
+                }
+            },
+            None => None,
+        };
+        let key: Vec<_> = location
+            .into_iter()
+            .chain(months.iter().map(|m| m[i].clone()))
+            .collect();
+        first_rows.entry(key.clone()).or_insert(i);
+
+        let species = species_keys(common_names[i].as_deref(), scientific_names[i].as_deref());
+        let seen = sightings.seen(list_type, location_codes);
+        if !species.is_empty()
+            && !species
+                .iter()
+                .any(|s| seen.is_some_and(|seen| seen.contains(s)))
+        {
+            keep[i] = true;
+            kept_locations.insert(key);
+        }
+    }
+    if !missing.is_empty() {
+        let mut missing: Vec<_> = missing.into_iter().collect();
+        missing.sort();
+        return Err(MagpieError::InvalidArgument(format!(
+            "Locations not found in the reference file: {}",
+            missing.join("; ")
+        ))
+        .into());
+    }
+
+    let mut blank = vec![false; frequencies.height()];
+    for (key, i) in first_rows {
+        if !kept_locations.contains(&key) {
+            keep[i] = true;
+            blank[i] = true;
+        }
+    }
+
+    let mut df = frequencies;
+    df.with_column(Series::new(KEEP, keep))?;
+    df.with_column(Series::new(BLANK, blank))?;
+    let blanked: Vec<_> = [COMMON_NAME, SCIENTIFIC_NAME, PERCENT]
+        .into_iter()
+        .filter(|&c| df.get_column_index(c).is_some())
+        .map(|c| when(col(BLANK)).then(lit(NULL)).otherwise(col(c)).alias(c))
+        .collect();
+    Ok(df
+        .lazy()
+        .filter(col(KEEP))
+        .with_columns(blanked)
+        .select([all().exclude([KEEP, BLANK])])
+        .collect()?)
+}
+
+pub(crate) fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let frequencies_file = matches
+        .get_one::<String>("frequencies")
+        .expect("Missing frequency file.");
+    let export_file = matches
+        .get_one::<String>("export")
+        .expect("Missing export file.");
+
+    let frequencies = load_data(frequencies_file)?;
+    let (location_level, reference_file) = if frequencies.get_column_index(HOTSPOT).is_some() {
+        (LocationLevel::Hotspot, matches.get_reference_files().1)
+    } else {
+        (LocationLevel::SubRegion, matches.get_reference_files().0)
+    };
+    // Unlike eBird's targets pages, every list type can be computed for hotspots, since the
+    // hotspot file has the region and country codes of each hotspot.
+    let list_type = if matches.get_flag("local") {
+        match location_level {
+            LocationLevel::Hotspot => ListType::Hotspot,
+            LocationLevel::SubRegion => ListType::SubRegion,
+        }
+    } else if matches.get_flag("region") {
+        ListType::Region
+    } else if matches.get_flag("country") {
+        ListType::Country
+    } else {
+        ListType::Global
+    };
+    let year = matches.get_flag("ytd").then(|| Local::now().year());
+    let sightings = Sightings::load(&load_data(export_file)?, year)?;
+    let reference = match list_type {
+        ListType::Global => None,
+        _ => Some(load_data(reference_file)?),
+    };
+
+    let mut targets = offline_targets(frequencies, &sightings, reference.as_ref(), &list_type)?;
+    info!(
+        "Computed {} targets for the {:?} list",
+        targets
+            .column(COMMON_NAME)?
+            .is_not_null()
+            .sum()
+            .unwrap_or(0),
+        list_type
+    );
+    write_table(
+        &mut targets,
+        matches.get_one::<String>("output").map(|s| s.as_str()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export() -> DataFrame {
+        df!(
+            EXPORT_COMMON_NAME => [
+                "Western Flycatcher",
+                "Black-throated Gray Warbler",
+                "Yellow-rumped Warbler (Audubon's)",
+                "Snow Goose",
+            ],
+            EXPORT_SCIENTIFIC_NAME => [
+                "Empidonax difficilis",
+                "Dendroica nigrescens",
+                "Setophaga coronata auduboni",
+                "Anser caerulescens",
+            ],
+            EXPORT_REGION => ["US-NY", "US-NY", "US-NY", "US-CA"],
+            EXPORT_SUB_REGION => [Some("Albany"), Some("Albany"), Some("Albany"), None],
+            EXPORT_LOCATION_ID => ["L1", "L1", "L1", "L2"],
+            EXPORT_DATE => ["2024-05-01", "2023-05-01", "2024-05-02", "2024-01-01"],
+        )
+        .unwrap()
+    }
+
+    fn reference() -> DataFrame {
+        df!(
+            COUNTRY => ["United States"; 3],
+            REGION => ["New York"; 3],
+            SUB_REGION => ["Albany", "Bronx", "Cayuga"],
+            ListType::Country.to_string().as_str() => ["US"; 3],
+            ListType::Region.to_string().as_str() => ["US-NY"; 3],
+            ListType::SubRegion.to_string().as_str() => ["US-NY-001", "US-NY-005", "US-NY-011"],
+        )
+        .unwrap()
+    }
+
+    fn frequencies() -> DataFrame {
+        let species = [
+            ("Pacific-slope Flycatcher", "Empidonax difficilis"),
+            ("Cordilleran Flycatcher", "Empidonax occidentalis"),
+            ("Black-throated Gray Warbler", "Setophaga nigrescens"),
+            ("Yellow-rumped Warbler", "Setophaga coronata"),
+        ];
+        let rows = species
+            .iter()
+            .map(|&s| ("Albany", s))
+            .chain([("Bronx", species[0]), ("Cayuga", species[2])]);
+        let (sub_regions, names): (Vec<_>, Vec<_>) = rows.unzip();
+        let (common, scientific): (Vec<_>, Vec<_>) = names.into_iter().unzip();
+        let height = sub_regions.len();
+        df!(
+            COMMON_NAME => common,
+            SCIENTIFIC_NAME => scientific,
+            PERCENT => vec![10.0f32; height],
+            COUNTRY => vec!["United States"; height],
+            REGION => vec!["New York"; height],
+            SUB_REGION => sub_regions,
+            START_MONTH => vec![1u32; height],
+            END_MONTH => vec![12u32; height],
+        )
+        .unwrap()
+    }
+
+    fn column(df: &DataFrame, name: &str) -> Vec<Option<String>> {
+        strings(df, name).unwrap()
+    }
+
+    #[test]
+    fn species_keys_match_by_scientific_and_common_name() {
+        assert_eq!(
+            species_keys(
+                Some("American Robin (migratorius)"),
+                Some("Turdus migratorius migratorius")
+            ),
+            vec!["scientific:turdus migratorius", "common:american robin"]
+        );
+        assert_eq!(
+            species_keys(Some("duck sp."), Some("")),
+            vec!["common:duck sp."]
+        );
+        assert!(species_keys(None, None).is_empty());
+    }
+
+    #[test]
+    fn seen_looks_up_the_list_of_a_location() {
+        let sightings = Sightings::load(&export(), None).unwrap();
+        let codes = |sub_region: &str, name: &str| LocationCodes {
+            country: "US".to_string(),
+            region: "US-NY".to_string(),
+            sub_region: sub_region.to_string(),
+            sub_region_name: name.to_string(),
+            hotspot: Some("L1".to_string()),
+        };
+        let albany = codes("US-NY-001", "Albany");
+        let seen = |list_type: ListType, codes: Option<&LocationCodes>| {
+            sightings.seen(&list_type, codes).cloned()
+        };
+        let snow_goose = "common:snow goose".to_string();
+
+        assert!(seen(ListType::Global, None).unwrap().contains(&snow_goose));
+        assert!(seen(ListType::Country, Some(&albany))
+            .unwrap()
+            .contains(&snow_goose));
+        assert!(!seen(ListType::Region, Some(&albany))
+            .unwrap()
+            .contains(&snow_goose));
+        assert_eq!(seen(ListType::SubRegion, Some(&albany)).unwrap().len(), 6);
+        assert_eq!(seen(ListType::Hotspot, Some(&albany)).unwrap().len(), 6);
+        // A region without sub-regions is its own sub-region.
+        let region = codes("US-NY", "New York");
+        assert_eq!(seen(ListType::SubRegion, Some(&region)).unwrap().len(), 6);
+        let bronx = codes("US-NY-005", "Bronx");
+        assert!(seen(ListType::SubRegion, Some(&bronx)).is_none());
+        assert!(seen(ListType::Region, None).is_none());
+
+        let this_year = Sightings::load(&export(), Some(2024)).unwrap();
+        assert!(!this_year
+            .global
+            .contains("common:black-throated gray warbler"));
+    }
+
+    #[test]
+    fn offline_targets_drops_species_seen_across_taxonomy_changes() {
+        let sightings = Sightings::load(&export(), None).unwrap();
+        let targets = offline_targets(
+            frequencies(),
+            &sightings,
+            Some(&reference()),
+            &ListType::SubRegion,
+        )
+        .unwrap();
+
+        // In Albany, the flycatcher split keeps its scientific name, the warbler's genus change
+        // keeps its common name, and the Audubon's group is lumped into its species. Only the
+        // new species from the split is left. The Bronx has no sightings, so its species stay.
+        assert_eq!(
+            column(&targets, COMMON_NAME),
+            vec![
+                Some("Cordilleran Flycatcher".to_string()),
+                Some("Pacific-slope Flycatcher".to_string()),
+                Some("Black-throated Gray Warbler".to_string()),
+            ]
+        );
+        assert_eq!(
+            column(&targets, SUB_REGION),
+            vec![
+                Some("Albany".to_string()),
+                Some("Bronx".to_string()),
+                Some("Cayuga".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn offline_targets_keeps_a_row_for_locations_without_targets() {
+        let sightings = Sightings::load(&export(), None).unwrap();
+        let mut albany = frequencies();
+        albany
+            .with_column(Series::new(SUB_REGION, vec!["Albany"; 6]))
+            .unwrap();
+        let targets = offline_targets(albany, &sightings, None, &ListType::Global).unwrap();
+
+        assert_eq!(
+            column(&targets, COMMON_NAME),
+            vec![Some("Cordilleran Flycatcher".to_string())]
+        );
+
+        let mut seen_everything = export();
+        seen_everything
+            .with_column(Series::new(
+                EXPORT_SCIENTIFIC_NAME,
+                ["Empidonax occidentalis"; 4],
+            ))
+            .unwrap();
+        let sightings = Sightings::load(&seen_everything, None).unwrap();
+        let frequencies = frequencies().slice(1, 1);
+        let targets = offline_targets(frequencies, &sightings, None, &ListType::Global).unwrap();
+        assert_eq!(targets.height(), 1);
+        assert_eq!(column(&targets, COMMON_NAME), vec![None]);
+        assert_eq!(column(&targets, PERCENT), vec![None]);
+    }
+
+    #[test]
+    fn offline_targets_rejects_unknown_locations() {
+        let sightings = Sightings::load(&export(), None).unwrap();
+        let reference = reference().slice(0, 1);
+        let result = offline_targets(
+            frequencies(),
+            &sightings,
+            Some(&reference),
+            &ListType::Region,
+        );
+        assert!(result.unwrap_err().to_string().contains("Bronx"));
+    }
+}
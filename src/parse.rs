@@ -1,109 +1,521 @@
 use crate::app::AppType;
+use crate::config::apply_profile;
+use crate::error::MagpieError;
+use crate::logging::LogFormat;
+use crate::notify::{Notifications, Notifier};
 use crate::target::scrape_params::LocationLevel::{Hotspot, SubRegion};
 use crate::target::scrape_params::{DateRange, ListType, LocationLevel};
-use clap::{arg, value_parser, Arg, ArgGroup, ArgMatches, Command};
+use chrono::{Datelike, Local};
+use clap::{arg, value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use std::env;
+use std::time::Duration;
 
+static DEFAULT_HOTSPOTS: &str = "hotspots_pl.csv";
 static DEFAULT_LOCATION: &str = "regions.csv";
 
-pub(crate) fn parse() -> ArgMatches {
-    Command::new("magpie")
-        .arg(arg!(--species))
-        .arg(arg!(--location))
-        .group(
-            ArgGroup::new("app")
-                .args(["species", "location"])
-                .required(true),
+/// Parses a range of months given as start and end month separated by a dash, e.g. `3-5`.
+fn parse_range(range: &str) -> Result<(u8, u8), String> {
+    let (start, end) = range
+        .split_once('-')
+        .ok_or_else(|| format!("Expected START-END, found {}", range))?;
+    let parse_month = |month: &str| {
+        month
+            .trim()
+            .parse::<u8>()
+            .ok()
+            .filter(|m| (1..=12).contains(m))
+            .ok_or_else(|| format!("Invalid month {} in range {}", month, range))
+    };
+    Ok((parse_month(start)?, parse_month(end)?))
+}
+
+/// Parses a watch interval given as a whole number and a unit of s, m, h, d, or w, e.g. `7d`.
+fn parse_interval(interval: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "Expected a number and one of s, m, h, d, or w, found {}",
+            interval
         )
+    };
+    let (count, unit) = match interval.char_indices().last() {
+        Some((i, unit)) => (&interval[..i], unit),
+        None => return Err(invalid()),
+    };
+    let seconds = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    count
+        .parse::<u64>()
+        .ok()
+        .filter(|&c| c > 0)
+        .map(|c| Duration::from_secs(c * seconds))
+        .ok_or_else(invalid)
+}
+
+/// Parses a rate limit in requests per second. The rate must be positive and finite, and not so
+/// small that the time between requests overflows a `Duration`.
+fn parse_rate_limit(rate: &str) -> Result<f64, String> {
+    rate.parse::<f64>()
+        .ok()
+        .filter(|&r| r.is_finite() && r > 0.0)
+        .filter(|&r| Duration::try_from_secs_f64(1.0 / r).is_ok())
+        .ok_or_else(|| format!("Invalid rate limit {}", rate))
+}
+
+fn targets_command() -> Command {
+    scrape_args(Command::new("targets").about("Scrapes target species for a list of locations"))
+}
+
+fn watch_command() -> Command {
+    scrape_args(Command::new("watch").about("Scrapes targets on a schedule and reports changes"))
+        .arg(
+            arg!(--every <INTERVAL> "Time between runs, e.g. 12h or 7d")
+                .value_parser(parse_interval)
+                .required(true)
+                .conflicts_with_all(["dry_run", "retry_failures"]),
+        )
+        .arg(
+            arg!(--snapshots <DIR> "Directory the output of each run is kept in")
+                .default_value("snapshots"),
+        )
+        .arg(
+            arg!(--threshold <PERCENT> "Smallest frequency change reported")
+                .value_parser(value_parser!(f64))
+                .default_value("5"),
+        )
+}
+
+/// Parses a year of eBird records, from the first year eBird accepts records for.
+fn parse_year(year: &str) -> Result<u16, String> {
+    year.parse::<u16>()
+        .ok()
+        .filter(|y| (1900..=9999).contains(y))
+        .ok_or_else(|| format!("Invalid year {}", year))
+}
+
+fn barchart_command() -> Command {
+    location_args(
+        Command::new("barchart")
+            .about("Scrapes weekly species frequencies for a list of locations"),
+    )
+    .arg(arg!(--start_year <YEAR> "First year of records").value_parser(parse_year))
+    .arg(
+        arg!(--end_year <YEAR> "Last year of records, this year if not given")
+            .value_parser(parse_year),
+    )
+    .arg(arg!(--output <OUTPUT>).required(true))
+    .arg(
+        arg!(--rate_limit <REQUESTS_PER_SECOND> "Most requests sent per second")
+            .value_parser(parse_rate_limit),
+    )
+    .arg(
+        arg!(--concurrency <PAGES> "Most pages fetched at once").value_parser(value_parser!(usize)),
+    )
+}
+
+/// Adds the arguments choosing the locations to scrape, shared by `targets`, `watch`, and
+/// `barchart`.
+fn location_args(command: Command) -> Command {
+    command
+        .arg(arg!(--hotspot <HOTSPOT>))
+        .arg(arg!(--subregion <SUBREGION>))
+        .arg(
+            Arg::new("codes")
+                .long("codes")
+                .value_name("CODES")
+                .value_delimiter(','),
+        )
+        .group(ArgGroup::new("list-type").args(["hotspot", "subregion", "codes"]))
+        .arg(arg!(--region_file <REGION_FILE>).requires("codes"))
+        .arg(arg!(--hotspot_file <HOTSPOT_FILE>).requires("codes"))
+}
+
+/// Adds the arguments of a scrape, shared by `targets` and `watch`.
+fn scrape_args(command: Command) -> Command {
+    location_args(command)
         .arg(arg!(--local))
-        .arg(arg!(--region))
-        .arg(arg!(--country))
+        .arg(arg!(--region).conflicts_with("hotspot"))
+        .arg(arg!(--country).conflicts_with("hotspot"))
         .arg(arg!(--global))
-        .group(ArgGroup::new("list_type").args(["local", "region", "country", "global"]))
+        .group(
+            ArgGroup::new("list_type")
+                .args(["local", "region", "country", "global"])
+                .required(true),
+        )
         .arg(arg!(--life))
         .arg(arg!(--ytd))
         .arg(arg!(--current_month))
         .arg(arg!(--date))
-        .group(ArgGroup::new("date_range").args(["life", "ytd", "current_month", "date"]))
+        .group(
+            ArgGroup::new("date_range")
+                .args(["life", "ytd", "current_month", "date"])
+                .required(true),
+        )
         .arg(arg!(--year))
         .arg(arg!(--all))
         .arg(
             Arg::new("month")
                 .long("month")
                 .required(false)
-                .value_parser(value_parser!(u8)),
+                .value_parser(value_parser!(u8).range(1..=12)),
         )
-        .arg(arg!(--range <RANGE>))
-        .group(ArgGroup::new("time_range").args(["year", "month", "all", "range"]))
-        .arg(arg!(--hotspot <HOTSPOT>))
-        .arg(arg!(--subregion <SUBREGION>))
-        .group(ArgGroup::new("list-type").args(["hotspot", "subregion"]))
-        .arg(arg!(--output <OUTPUT>))
-        .get_matches()
+        .arg(arg!(--range <RANGE>).value_parser(parse_range))
+        .group(
+            ArgGroup::new("time_range")
+                .args(["year", "month", "all", "range"])
+                .required(true),
+        )
+        .arg(arg!(--output <OUTPUT>).required_unless_present("dry_run"))
+        .arg(arg!(--dry_run "Prints the planned requests without logging in or fetching"))
+        .arg(
+            arg!(--rate_limit <REQUESTS_PER_SECOND> "Most requests sent per second")
+                .value_parser(parse_rate_limit),
+        )
+        .arg(
+            arg!(--concurrency <PAGES> "Most pages fetched at once")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(arg!(--skip_check "Skips checking the page selectors before scraping"))
+        .arg(arg!(--wide))
+        .arg(
+            arg!(--retry_failures <FAILURES_FILE> "Scrapes only the failed pages of an earlier run")
+                .conflicts_with("wide"),
+        )
+        .arg(arg!(--confidence).conflicts_with("wide"))
+        .arg(
+            arg!(--z <Z> "Standard normal quantile of the confidence level")
+                .value_parser(value_parser!(f64))
+                .default_value("1.96")
+                .requires("confidence"),
+        )
+        .arg(
+            arg!(--max_width <PERCENT> "Widest interval counted as reliable")
+                .value_parser(value_parser!(f64))
+                .default_value("20")
+                .requires("confidence"),
+        )
+        .arg(arg!(--notify_webhook <URL> "Posts new targets as JSON to the URL"))
+        .arg(
+            arg!(--notify_smtp <SERVER> "Emails new targets through the SMTP server at host:port")
+                .requires("notify_to"),
+        )
+        .arg(
+            Arg::new("notify_to")
+                .long("notify_to")
+                .value_name("ADDRESSES")
+                .value_delimiter(',')
+                .requires("notify_smtp"),
+        )
+        .arg(
+            arg!(--notify_from <ADDRESS>)
+                .default_value("magpie@localhost")
+                .requires("notify_smtp"),
+        )
+        .arg(
+            arg!(--notify_threshold <PERCENT> "Lowest frequency of a new target worth notifying")
+                .value_parser(value_parser!(f64))
+                .default_value("10"),
+        )
+        .arg(arg!(--profile <PROFILE> "Named profile of run parameters"))
+        .arg(arg!(--config <CONFIG> "Config file with profiles"))
+}
+
+fn report_command() -> Command {
+    Command::new("report")
+        .about("Summarizes a magpie output file")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("rank")
+                .about("Ranks locations by expected number of new species")
+                .arg(arg!(<input> "Magpie output file"))
+                .arg(
+                    arg!(--threshold <PERCENT>)
+                        .value_parser(value_parser!(f32))
+                        .default_value("10"),
+                )
+                .arg(arg!(--output <OUTPUT>)),
+        )
+        .subcommand(
+            Command::new("confidence")
+                .about("Adds confidence intervals for frequencies")
+                .arg(arg!(<input> "Magpie output file"))
+                .arg(
+                    arg!(--z <Z> "Standard normal quantile of the confidence level")
+                        .value_parser(value_parser!(f64))
+                        .default_value("1.96"),
+                )
+                .arg(
+                    arg!(--max_width <PERCENT> "Widest interval counted as reliable")
+                        .value_parser(value_parser!(f64))
+                        .default_value("20"),
+                )
+                .arg(arg!(--output <OUTPUT>)),
+        )
+        .subcommand(
+            Command::new("html")
+                .about("Renders a magpie output file as a single HTML page")
+                .arg(arg!(<input> "Magpie output file"))
+                .arg(arg!(--title <TITLE>).default_value("Magpie Targets"))
+                .arg(
+                    arg!(--threshold <PERCENT>)
+                        .value_parser(value_parser!(f32))
+                        .default_value("10"),
+                )
+                .arg(arg!(--output <OUTPUT>)),
+        )
+        .subcommand(
+            Command::new("plan")
+                .about("Chooses the locations that maximize expected new species")
+                .arg(arg!(<input> "Magpie output file"))
+                .arg(
+                    arg!(--locations <LOCATIONS>)
+                        .value_parser(value_parser!(usize))
+                        .required(true),
+                )
+                .arg(arg!(--per_month))
+                .arg(arg!(--output <OUTPUT>)),
+        )
+}
+
+pub(crate) fn parse() -> Result<ArgMatches, MagpieError> {
+    let args = apply_profile(env::args_os())?;
+    Ok(Command::new("magpie")
+        .subcommand_required(true)
+        .arg(
+            arg!(-v --verbose "Logs more detail, repeat for more")
+                .action(ArgAction::Count)
+                .global(true),
+        )
+        .arg(
+            arg!(-q --quiet "Logs only warnings, repeat for errors only")
+                .action(ArgAction::Count)
+                .global(true),
+        )
+        .arg(arg!(--log_file <LOG_FILE> "Also writes the log to a file").global(true))
+        .arg(
+            arg!(--selectors <SELECTOR_FILE> "TOML file overriding the built in page selectors")
+                .global(true),
+        )
+        .arg(
+            arg!(--log_format <LOG_FORMAT>)
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .global(true),
+        )
+        .subcommand(targets_command())
+        .subcommand(watch_command())
+        .subcommand(barchart_command())
+        .subcommand(
+            Command::new("locations").about("Scrapes all regions, sub-regions, and hotspots"),
+        )
+        .subcommand(report_command())
+        .subcommand(
+            Command::new("diff")
+                .about("Compares two magpie output files")
+                .arg(arg!(<old> "Earlier magpie output file"))
+                .arg(arg!(<new> "Later magpie output file"))
+                .arg(
+                    arg!(--threshold <PERCENT>)
+                        .value_parser(value_parser!(f64))
+                        .default_value("5"),
+                )
+                .arg(arg!(--output <OUTPUT>)),
+        )
+        .subcommand(
+            Command::new("where")
+                .about("Finds the best locations and months for a species")
+                .arg(arg!(<species> "Common or scientific name"))
+                .arg(arg!(<input> "Magpie output file"))
+                .arg(
+                    arg!(--prior_checklists <CHECKLISTS>)
+                        .value_parser(value_parser!(f64))
+                        .default_value("10"),
+                )
+                .arg(
+                    arg!(--limit <LIMIT>)
+                        .value_parser(value_parser!(u32))
+                        .default_value("20"),
+                )
+                .arg(arg!(--output <OUTPUT>)),
+        )
+        .subcommand(
+            Command::new("offline")
+                .about("Computes targets from an eBird data export without logging in")
+                .arg(arg!(--export <EXPORT> "My eBird Data csv").required(true))
+                .arg(
+                    arg!(--frequencies <FREQUENCIES> "Magpie output file of a --local run")
+                        .required(true),
+                )
+                .arg(arg!(--local))
+                .arg(arg!(--region))
+                .arg(arg!(--country))
+                .arg(arg!(--global))
+                .group(
+                    ArgGroup::new("list_type")
+                        .args(["local", "region", "country", "global"])
+                        .required(true),
+                )
+                .arg(arg!(--ytd "Counts only species seen this year"))
+                .arg(arg!(--region_file <REGION_FILE>))
+                .arg(arg!(--hotspot_file <HOTSPOT_FILE>))
+                .arg(arg!(--output <OUTPUT>)),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Checks that the page selectors still match eBird's pages"),
+        )
+        .get_matches_from(args))
 }
 
 pub(crate) trait MagpieParse {
-    fn get_app(&self) -> AppType;
-    fn get_date_range(&self) -> DateRange;
-    fn get_list_type(&self) -> ListType;
+    fn get_app(&self) -> Result<(AppType, &ArgMatches), MagpieError>;
+    fn get_barchart_years(&self) -> Result<(u16, u16), MagpieError>;
+    fn get_concurrency(&self) -> Option<usize>;
+    fn get_confidence(&self) -> Option<(f64, f64)>;
+    fn get_date_range(&self) -> Result<DateRange, MagpieError>;
+    fn get_dry_run(&self) -> bool;
+    fn get_every(&self) -> Duration;
+    fn get_list_type(&self, location_level: &LocationLevel) -> Result<ListType, MagpieError>;
+    fn get_loc_codes(&self) -> Option<Vec<&str>>;
     fn get_loc_data(&self) -> (&str, LocationLevel);
-    fn get_output_file(&self) -> &str;
-    fn get_time_range(&self) -> Vec<(u8, u8)>;
+    fn get_loc_file(&self, location_level: &LocationLevel) -> &str;
+    fn get_log_settings(&self) -> (u8, u8, Option<&str>, LogFormat);
+    fn get_notifications(&self) -> Option<Notifications>;
+    fn get_output_file(&self) -> Result<&str, MagpieError>;
+    fn get_rate_limit(&self) -> Option<f64>;
+    fn get_reference_files(&self) -> (&str, &str);
+    fn get_retry_file(&self) -> Option<&str>;
+    fn get_selector_file(&self) -> Option<&str>;
+    fn get_skip_check(&self) -> bool;
+    fn get_snapshot_dir(&self) -> &str;
+    fn get_time_range(&self) -> Result<Vec<(u8, u8)>, MagpieError>;
+    fn get_wide_output(&self) -> bool;
 }
 
 impl MagpieParse for ArgMatches {
-    fn get_app(&self) -> AppType {
-        if self.get_flag("species") {
-            AppType::Species
-        } else if self.get_flag("location") {
-            AppType::Location
-        } else {
-            panic!("Invalid selection for application.")
+    fn get_app(&self) -> Result<(AppType, &ArgMatches), MagpieError> {
+        let (name, app_matches) = self
+            .subcommand()
+            .ok_or_else(|| MagpieError::InvalidArgument("Missing command.".to_string()))?;
+        let app = match name {
+            "targets" => AppType::Species,
+            "locations" => AppType::Location,
+            "diff" => AppType::Diff,
+            "report" => AppType::Report,
+            "where" => AppType::Where,
+            "doctor" => AppType::Doctor,
+            "watch" => AppType::Watch,
+            "offline" => AppType::Offline,
+            "barchart" => AppType::BarChart,
+            _ => {
+                return Err(MagpieError::InvalidArgument(format!(
+                    "Invalid command {}.",
+                    name
+                )))
+            }
+        };
+        Ok((app, app_matches))
+    }
+
+    fn get_barchart_years(&self) -> Result<(u16, u16), MagpieError> {
+        let start = self.get_one::<u16>("start_year").copied().unwrap_or(1900);
+        let end = self
+            .get_one::<u16>("end_year")
+            .copied()
+            .unwrap_or_else(|| Local::now().year() as u16);
+        if start > end {
+            return Err(MagpieError::InvalidArgument(format!(
+                "--start_year {} is after --end_year {}.",
+                start, end
+            )));
         }
+        Ok((start, end))
     }
 
-    fn get_date_range(&self) -> DateRange {
+    fn get_concurrency(&self) -> Option<usize> {
+        self.get_one::<usize>("concurrency").copied()
+    }
+
+    fn get_confidence(&self) -> Option<(f64, f64)> {
+        self.get_flag("confidence").then(|| {
+            (
+                *self.get_one::<f64>("z").expect("Missing z."),
+                *self
+                    .get_one::<f64>("max_width")
+                    .expect("Missing max width."),
+            )
+        })
+    }
+
+    fn get_date_range(&self) -> Result<DateRange, MagpieError> {
         match (
             self.get_flag("life"),
             self.get_flag("ytd"),
             self.get_flag("current_month"),
             self.get_flag("date"),
         ) {
-            (true, _, _, _) => DateRange::Life,
-            (_, true, _, _) => DateRange::Year,
-            (_, _, true, _) => DateRange::Month,
-            (_, _, _, true) => DateRange::Date,
-            _ => panic!("Invalid Date Range."),
+            (true, _, _, _) => Ok(DateRange::Life),
+            (_, true, _, _) => Ok(DateRange::Year),
+            (_, _, true, _) => Ok(DateRange::Month),
+            (_, _, _, true) => Ok(DateRange::Date),
+            _ => Err(MagpieError::InvalidArgument(
+                "Missing date range: use one of --life, --ytd, --current_month, or --date."
+                    .to_string(),
+            )),
         }
     }
 
-    fn get_list_type(&self) -> ListType {
-        if self.get_one::<String>("hotspot").is_some() {
-            return if self.get_flag("local") {
-                ListType::Hotspot
-            } else if self.get_flag("global") {
-                ListType::Global
-            } else {
-                panic!("Invalid List Type for hotspot list.")
-            };
-        }
+    fn get_dry_run(&self) -> bool {
+        self.get_flag("dry_run")
+    }
+
+    fn get_every(&self) -> Duration {
+        *self
+            .get_one::<Duration>("every")
+            .expect("Missing watch interval.")
+    }
 
-        match self.get_one::<String>("subregion") {
-            Some(_) => match (
+    fn get_list_type(&self, location_level: &LocationLevel) -> Result<ListType, MagpieError> {
+        match location_level {
+            Hotspot => {
+                if self.get_flag("local") {
+                    Ok(ListType::Hotspot)
+                } else if self.get_flag("global") {
+                    Ok(ListType::Global)
+                } else {
+                    Err(MagpieError::InvalidArgument(
+                        "Hotspots only support --local and --global lists.".to_string(),
+                    ))
+                }
+            }
+            SubRegion => match (
                 self.get_flag("local"),
                 self.get_flag("region"),
                 self.get_flag("country"),
                 self.get_flag("global"),
             ) {
-                (true, _, _, _) => ListType::SubRegion,
-                (_, true, _, _) => ListType::Region,
-                (_, _, true, _) => ListType::Country,
-                (_, _, _, true) => ListType::Global,
-                _ => panic!("Invalid List Type for geo list."),
+                (true, _, _, _) => Ok(ListType::SubRegion),
+                (_, true, _, _) => Ok(ListType::Region),
+                (_, _, true, _) => Ok(ListType::Country),
+                (_, _, _, true) => Ok(ListType::Global),
+                _ => Err(MagpieError::InvalidArgument(
+                    "Missing list type: use one of --local, --region, --country, or --global."
+                        .to_string(),
+                )),
             },
-            None => panic!("Invalid List Type."),
         }
     }
 
+    fn get_loc_codes(&self) -> Option<Vec<&str>> {
+        self.get_many::<String>("codes")
+            .map(|codes| codes.map(|c| c.trim()).filter(|c| !c.is_empty()).collect())
+    }
+
     fn get_loc_data(&self) -> (&str, LocationLevel) {
         self.get_one::<String>("hotspot")
             .map(|f| (f.as_str(), Hotspot))
@@ -114,28 +526,156 @@ impl MagpieParse for ArgMatches {
             .unwrap_or((DEFAULT_LOCATION, SubRegion))
     }
 
-    fn get_output_file(&self) -> &str {
+    fn get_loc_file(&self, location_level: &LocationLevel) -> &str {
+        if self.get_loc_codes().is_none() {
+            return self.get_loc_data().0;
+        }
+        let (region_file, hotspot_file) = self.get_reference_files();
+        match location_level {
+            Hotspot => hotspot_file,
+            SubRegion => region_file,
+        }
+    }
+
+    fn get_log_settings(&self) -> (u8, u8, Option<&str>, LogFormat) {
+        let format = match self.get_one::<String>("log_format").map(|f| f.as_str()) {
+            Some("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        };
+        (
+            self.get_count("verbose"),
+            self.get_count("quiet"),
+            self.get_one::<String>("log_file").map(|f| f.as_str()),
+            format,
+        )
+    }
+
+    fn get_notifications(&self) -> Option<Notifications> {
+        let webhook = self
+            .get_one::<String>("notify_webhook")
+            .map(|url| Notifier::Webhook { url: url.clone() });
+        let smtp = self
+            .get_one::<String>("notify_smtp")
+            .map(|server| Notifier::Smtp {
+                server: server.clone(),
+                from: self
+                    .get_one::<String>("notify_from")
+                    .expect("Missing sender address.")
+                    .clone(),
+                to: self
+                    .get_many::<String>("notify_to")
+                    .expect("Missing recipient addresses.")
+                    .map(|to| to.trim().to_string())
+                    .collect(),
+            });
+        let notifiers: Vec<_> = webhook.into_iter().chain(smtp).collect();
+        (!notifiers.is_empty()).then(|| Notifications {
+            notifiers,
+            threshold: *self
+                .get_one::<f64>("notify_threshold")
+                .expect("Missing notification threshold."),
+        })
+    }
+
+    fn get_output_file(&self) -> Result<&str, MagpieError> {
         self.get_one::<String>("output")
-            .expect("Missing output file.")
+            .map(|f| f.as_str())
+            .ok_or_else(|| MagpieError::InvalidArgument("Missing output file.".to_string()))
+    }
+
+    fn get_rate_limit(&self) -> Option<f64> {
+        self.get_one::<f64>("rate_limit").copied()
+    }
+
+    fn get_reference_files(&self) -> (&str, &str) {
+        (
+            self.get_one::<String>("region_file")
+                .map_or(DEFAULT_LOCATION, |f| f.as_str()),
+            self.get_one::<String>("hotspot_file")
+                .map_or(DEFAULT_HOTSPOTS, |f| f.as_str()),
+        )
+    }
+
+    fn get_retry_file(&self) -> Option<&str> {
+        self.get_one::<String>("retry_failures").map(|f| f.as_str())
+    }
+
+    fn get_selector_file(&self) -> Option<&str> {
+        self.get_one::<String>("selectors").map(|f| f.as_str())
+    }
+
+    fn get_skip_check(&self) -> bool {
+        self.get_flag("skip_check")
+    }
+
+    fn get_snapshot_dir(&self) -> &str {
+        self.get_one::<String>("snapshots")
+            .map(|d| d.as_str())
+            .expect("Missing snapshot directory.")
     }
 
-    fn get_time_range(&self) -> Vec<(u8, u8)> {
+    fn get_time_range(&self) -> Result<Vec<(u8, u8)>, MagpieError> {
         if self.get_flag("year") {
-            vec![(1, 12)]
+            Ok(vec![(1, 12)])
         } else if self.get_flag("all") {
-            (1..=12).map(|m| (m, m)).collect()
+            Ok((1..=12).map(|m| (m, m)).collect())
         } else {
             self.get_one::<u8>("month")
                 .map(|&m| vec![(m, m)])
-                .or_else(|| {
-                    self.get_one::<String>("range").map(|range| {
-                        let r: Vec<&str> = range.split('-').collect();
-                        let start_month = r[0].parse::<u8>().expect("Invalid start month");
-                        let end_month = r[1].parse::<u8>().expect("Invalid end month");
-                        vec![(start_month, end_month)]
-                    })
+                .or_else(|| self.get_one::<(u8, u8)>("range").map(|&r| vec![r]))
+                .ok_or_else(|| {
+                    MagpieError::InvalidArgument(
+                        "Missing time range: use one of --year, --month, --all, or --range."
+                            .to_string(),
+                    )
                 })
-                .expect("Invalid time range.")
+        }
+    }
+
+    fn get_wide_output(&self) -> bool {
+        self.get_flag("wide")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_reads_start_and_end_months() {
+        assert_eq!(parse_range("3-5"), Ok((3, 5)));
+        assert_eq!(parse_range(" 11 - 2 "), Ok((11, 2)));
+    }
+
+    #[test]
+    fn parse_range_rejects_invalid_months() {
+        for range in ["", "3", "0-5", "3-13", "a-5", "3-"] {
+            assert!(parse_range(range).is_err(), "{}", range);
+        }
+    }
+
+    #[test]
+    fn parse_interval_reads_count_and_unit() {
+        assert_eq!(parse_interval("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("12h"), Ok(Duration::from_secs(12 * 60 * 60)));
+        assert_eq!(
+            parse_interval("2w"),
+            Ok(Duration::from_secs(2 * 7 * 24 * 60 * 60))
+        );
+    }
+
+    #[test]
+    fn parse_interval_rejects_invalid_intervals() {
+        for interval in ["", "d", "0d", "7", "7x", "-1d", "7é", "é"] {
+            assert!(parse_interval(interval).is_err(), "{}", interval);
+        }
+    }
+
+    #[test]
+    fn parse_rate_limit_rejects_unusable_rates() {
+        assert_eq!(parse_rate_limit("0.5"), Ok(0.5));
+        for rate in ["0", "-2", "NaN", "inf", "1e-30", "fast"] {
+            assert!(parse_rate_limit(rate).is_err(), "{}", rate);
         }
     }
 }
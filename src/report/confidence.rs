@@ -0,0 +1,92 @@
+use polars::prelude::*;
+
+use crate::report::{LOWER_PERCENT, RELIABLE, UPPER_PERCENT};
+use crate::target::{CHECKLISTS, PERCENT};
+
+/// Computes the Wilson score interval, in percent, for a frequency of `percent` over `checklists`
+/// checklists, where `z` is the standard normal quantile of the confidence level.
+/// With no checklists nothing is known and the interval covers everything.
+fn wilson_interval(percent: f64, checklists: f64, z: f64) -> (f64, f64) {
+    if checklists <= 0.0 {
+        return (0.0, 100.0);
+    }
+    let p = (percent / 100.0).clamp(0.0, 1.0);
+    let z2 = z * z;
+    let denominator = 1.0 + z2 / checklists;
+    let center = (p + z2 / (2.0 * checklists)) / denominator;
+    let half_width =
+        z * (p * (1.0 - p) / checklists + z2 / (4.0 * checklists * checklists)).sqrt() / denominator;
+    (
+        100.0 * (center - half_width).max(0.0),
+        100.0 * (center + half_width).min(1.0),
+    )
+}
+
+/// Adds a confidence interval for each frequency, based on the number of checklists behind it.
+///
+/// A frequency of 50% from two checklists and one from two thousand are very different, so the
+/// table gets three extra columns:
+/// - lower percent: The lower end of the Wilson score interval.
+/// - upper percent: The upper end of the Wilson score interval.
+/// - reliable: Whether the interval is no wider than `max_width` percentage points.
+///
+/// Rows without a frequency, e.g. locations whose list had no targets, get no interval.
+pub(crate) fn add_confidence(mut df: DataFrame, z: f64, max_width: f64) -> PolarsResult<DataFrame> {
+    let percent = df.column(PERCENT)?.cast(&DataType::Float64)?;
+    let checklists = df.column(CHECKLISTS)?.cast(&DataType::Float64)?;
+    let (lower, upper): (Vec<_>, Vec<_>) = percent
+        .f64()?
+        .into_iter()
+        .zip(checklists.f64()?)
+        .map(|(p, n)| match p {
+            Some(p) => {
+                let (l, u) = wilson_interval(p, n.unwrap_or(0.0), z);
+                (Some(l), Some(u))
+            }
+            None => (None, None),
+        })
+        .unzip();
+    let reliable: Vec<_> = lower
+        .iter()
+        .zip(&upper)
+        .map(|(l, u)| Some(u.as_ref()? - l.as_ref()? <= max_width))
+        .collect();
+
+    df.with_column(Series::new(LOWER_PERCENT, lower))?;
+    df.with_column(Series::new(UPPER_PERCENT, upper))?;
+    df.with_column(Series::new(RELIABLE, reliable))?;
+    Ok(df)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_interval(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 0.01 && (actual.1 - expected.1).abs() < 0.01,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn wilson_interval_matches_known_values() {
+        assert_interval(wilson_interval(50.0, 100.0, 1.96), (40.38, 59.62));
+        assert_interval(wilson_interval(0.0, 10.0, 1.96), (0.0, 27.75));
+        assert_interval(wilson_interval(100.0, 10.0, 1.96), (72.25, 100.0));
+    }
+
+    #[test]
+    fn wilson_interval_covers_everything_without_checklists() {
+        assert_eq!(wilson_interval(50.0, 0.0, 1.96), (0.0, 100.0));
+    }
+
+    #[test]
+    fn wilson_interval_narrows_with_more_checklists() {
+        let (few_lower, few_upper) = wilson_interval(50.0, 2.0, 1.96);
+        let (many_lower, many_upper) = wilson_interval(50.0, 2000.0, 1.96);
+        assert!(many_upper - many_lower < few_upper - few_lower);
+    }
+}
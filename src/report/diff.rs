@@ -0,0 +1,210 @@
+use polars::prelude::*;
+
+use crate::report::{location_columns, CHANGE, NEW_PERCENT, OLD_PERCENT, STATUS};
+use crate::target::outcome::{drop_pages, failed_pages};
+use crate::target::{COMMON_NAME, PERCENT, SCIENTIFIC_NAME};
+
+static CHANGED: &str = "changed";
+static LOST: &str = "lost";
+static NEW: &str = "new";
+static SEEN: &str = "seen";
+
+/// Compares two magpie output tables for the same locations and reports how the targets changed.
+///
+/// Rows are matched on location, month range, and species. Each reported row has one of three statuses:
+/// - new: The species is a target in the new table but not the old one.
+/// - lost: The species was a target in the old table but not the new one, e.g. because it has been seen.
+/// - changed: The species is a target in both and its frequency moved by at least `threshold` percentage points.
+///
+/// `failures` is the outcome table of the new run. Locations and months whose page failed in it
+/// are left out of both tables, so that their targets are not reported as lost.
+pub(crate) fn diff(
+    old: DataFrame,
+    new: DataFrame,
+    failures: Option<&DataFrame>,
+    threshold: f64,
+) -> PolarsResult<DataFrame> {
+    let old = drop_failed(old, failures)?;
+    let new = drop_failed(new, failures)?;
+    let keys = location_columns(&old);
+    polars_ensure!(
+        keys == location_columns(&new),
+        ComputeError: "Output files have different location columns."
+    );
+    let key_columns: Vec<_> = [COMMON_NAME, SCIENTIFIC_NAME]
+        .into_iter()
+        .chain(keys)
+        .map(col)
+        .collect();
+    let select_percent = |df: DataFrame, name: &str| {
+        df.lazy().filter(col(COMMON_NAME).is_not_null()).select(
+            key_columns
+                .iter()
+                .cloned()
+                .chain([col(PERCENT).cast(DataType::Float64).alias(name)])
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    let mut join_args = JoinArgs::new(JoinType::Full).with_coalesce(JoinCoalesce::CoalesceColumns);
+    join_args.join_nulls = true;
+    let change = col(CHANGE);
+    select_percent(old, OLD_PERCENT)
+        .join(
+            select_percent(new, NEW_PERCENT),
+            key_columns.clone(),
+            key_columns.clone(),
+            join_args,
+        )
+        .with_column((col(NEW_PERCENT) - col(OLD_PERCENT)).alias(CHANGE))
+        .with_column(
+            when(col(OLD_PERCENT).is_null())
+                .then(lit(NEW))
+                .when(col(NEW_PERCENT).is_null())
+                .then(lit(LOST))
+                .otherwise(lit(CHANGED))
+                .alias(STATUS),
+        )
+        .filter(
+            col(OLD_PERCENT)
+                .is_null()
+                .or(col(NEW_PERCENT).is_null())
+                .or(change.clone().gt_eq(lit(threshold)))
+                .or(change.lt_eq(lit(-threshold))),
+        )
+        .sort(
+            [STATUS],
+            SortMultipleOptions::default().with_maintain_order(true),
+        )
+        .collect()
+}
+
+/// Removes the rows of an output table for the pages that failed in an outcome table.
+fn drop_failed(df: DataFrame, failures: Option<&DataFrame>) -> PolarsResult<DataFrame> {
+    match failures {
+        Some(failures) => drop_pages(&df, failures, &failed_pages(failures)?),
+        None => Ok(df),
+    }
+}
+
+/// Counts the rows of a diff table with each status, returned as new, lost, and changed.
+pub(crate) fn count_changes(changes: &DataFrame) -> PolarsResult<(usize, usize, usize)> {
+    let statuses = changes.column(STATUS)?.str()?;
+    let count = |status: &str| statuses.into_iter().filter(|&s| s == Some(status)).count();
+    Ok((count(NEW), count(LOST), count(CHANGED)))
+}
+
+/// Returns the rows of a new magpie output table for species that were not targets at the same
+/// location and month range in the old table and have a frequency of at least `threshold` percent.
+/// The rows keep the columns of the new table. Locations and months whose page failed in
+/// `failures`, the outcome table of the new run, are left out.
+pub(crate) fn new_targets(
+    old: DataFrame,
+    new: DataFrame,
+    failures: Option<&DataFrame>,
+    threshold: f64,
+) -> PolarsResult<DataFrame> {
+    let new = drop_failed(new, failures)?;
+    let keys = location_columns(&new);
+    polars_ensure!(
+        keys == location_columns(&old),
+        ComputeError: "Output files have different location columns."
+    );
+    let key_columns: Vec<_> = [COMMON_NAME, SCIENTIFIC_NAME]
+        .into_iter()
+        .chain(keys)
+        .map(col)
+        .collect();
+
+    let mut join_args = JoinArgs::new(JoinType::Left);
+    join_args.join_nulls = true;
+    new.lazy()
+        .filter(
+            col(COMMON_NAME)
+                .is_not_null()
+                .and(col(PERCENT).cast(DataType::Float64).gt_eq(lit(threshold))),
+        )
+        .join(
+            old.lazy()
+                .select(key_columns.clone())
+                .unique(None, UniqueKeepStrategy::First)
+                .with_column(lit(true).alias(SEEN)),
+            key_columns.clone(),
+            key_columns,
+            join_args,
+        )
+        .filter(col(SEEN).is_null())
+        .select([all().exclude([SEEN])])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::{COUNTRY, END_MONTH, REGION, START_MONTH, SUB_REGION};
+
+    fn output(rows: &[(&str, &str, f64)]) -> DataFrame {
+        df!(
+            COMMON_NAME => rows.iter().map(|r| r.0).collect::<Vec<_>>(),
+            SCIENTIFIC_NAME => rows.iter().map(|_| "").collect::<Vec<_>>(),
+            PERCENT => rows.iter().map(|r| r.2).collect::<Vec<_>>(),
+            COUNTRY => rows.iter().map(|_| "US").collect::<Vec<_>>(),
+            REGION => rows.iter().map(|r| r.1).collect::<Vec<_>>(),
+            SUB_REGION => rows.iter().map(|_| None::<&str>).collect::<Vec<_>>(),
+            START_MONTH => rows.iter().map(|_| 1i64).collect::<Vec<_>>(),
+            END_MONTH => rows.iter().map(|_| 12i64).collect::<Vec<_>>(),
+        )
+        .unwrap()
+    }
+
+    fn failures() -> DataFrame {
+        df!(
+            "code" => ["US-CA", "US-NY"],
+            COUNTRY => ["US", "US"],
+            REGION => ["US-CA", "US-NY"],
+            SUB_REGION => [None::<&str>, None],
+            START_MONTH => [1u32, 1],
+            END_MONTH => [12u32, 12],
+            "outcome" => ["gave_up", "ok"],
+        )
+        .unwrap()
+    }
+
+    fn statuses(df: &DataFrame) -> Vec<(String, String)> {
+        let names = df.column(COMMON_NAME).unwrap().str().unwrap();
+        let statuses = df.column(STATUS).unwrap().str().unwrap();
+        names
+            .into_iter()
+            .zip(statuses)
+            .map(|(n, s)| (n.unwrap().to_string(), s.unwrap().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn diff_leaves_out_pages_that_failed_in_the_new_run() {
+        let old = output(&[("Wrentit", "US-CA", 20.0), ("Fish Crow", "US-NY", 10.0)]);
+        let new = output(&[("Brant", "US-NY", 30.0)]);
+
+        let changes = diff(old.clone(), new.clone(), None, 5.0).unwrap();
+        assert_eq!(changes.height(), 3);
+
+        let changes = diff(old, new, Some(&failures()), 5.0).unwrap();
+        assert_eq!(
+            statuses(&changes),
+            vec![
+                ("Fish Crow".to_string(), LOST.to_string()),
+                ("Brant".to_string(), NEW.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn new_targets_leaves_out_pages_that_failed_in_the_new_run() {
+        let old = output(&[]);
+        let new = output(&[("Wrentit", "US-CA", 20.0), ("Brant", "US-NY", 30.0)]);
+
+        let targets = new_targets(old, new, Some(&failures()), 5.0).unwrap();
+        let names = targets.column(COMMON_NAME).unwrap().str().unwrap();
+        assert_eq!(names.into_iter().collect::<Vec<_>>(), vec![Some("Brant")]);
+    }
+}
@@ -0,0 +1,62 @@
+use polars::prelude::*;
+
+use crate::report::{location_columns, WEIGHTED_PERCENT};
+use crate::target::{CHECKLISTS, COMMON_NAME, PERCENT, SCIENTIFIC_NAME};
+
+/// Builds a mask of the rows whose common or scientific name matches `species`, ignoring case.
+fn species_mask(df: &DataFrame, species: &str) -> PolarsResult<BooleanChunked> {
+    let species = species.trim().to_lowercase();
+    let common_names = df.column(COMMON_NAME)?.str()?;
+    let scientific_names = df.column(SCIENTIFIC_NAME)?.str()?;
+    Ok(common_names
+        .into_iter()
+        .zip(scientific_names)
+        .map(|(common, scientific)| {
+            [common, scientific]
+                .into_iter()
+                .flatten()
+                .any(|name| name.to_lowercase() == species)
+        })
+        .collect())
+}
+
+/// Finds the locations and month ranges where a species is most likely to be seen.
+///
+/// Frequencies are weighted by the number of checklists behind them, so a species reported on
+/// the only checklist for a location does not outrank one reported on half of a thousand
+/// checklists. The weighted percent treats `prior_checklists` extra checklists without the
+/// species as already submitted:
+///
+/// `weighted percent = percent * checklists / (checklists + prior_checklists)`
+///
+/// Returns at most `limit` rows, sorted by weighted percent.
+pub(crate) fn find_species(
+    df: DataFrame,
+    species: &str,
+    prior_checklists: f64,
+    limit: u32,
+) -> PolarsResult<DataFrame> {
+    let mask = species_mask(&df, species)?;
+    let columns: Vec<_> = [COMMON_NAME, SCIENTIFIC_NAME]
+        .into_iter()
+        .chain(location_columns(&df))
+        .chain([PERCENT, CHECKLISTS, WEIGHTED_PERCENT])
+        .map(col)
+        .collect();
+
+    let checklists = col(CHECKLISTS).cast(DataType::Float64);
+    df.filter(&mask)?
+        .lazy()
+        .with_column(
+            (col(PERCENT).cast(DataType::Float64) * checklists.clone()
+                / (checklists + lit(prior_checklists)))
+            .alias(WEIGHTED_PERCENT),
+        )
+        .select(columns)
+        .sort(
+            [WEIGHTED_PERCENT],
+            SortMultipleOptions::default().with_order_descending(true),
+        )
+        .limit(limit)
+        .collect()
+}
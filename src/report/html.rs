@@ -0,0 +1,302 @@
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt::{self, Write};
+
+use itertools::Itertools;
+use polars::prelude::*;
+
+use crate::report::rank::rank;
+use crate::report::string_column;
+use crate::target::{
+    month_label, CHECKLISTS, COMMON_NAME, COUNTRY, END_MONTH, HOTSPOT, PERCENT, REGION,
+    SCIENTIFIC_NAME, START_MONTH, SUB_REGION,
+};
+
+static STYLE: &str = r#"
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.6em; text-align: left; }
+th { background: #eee; }
+table.sortable th { cursor: pointer; }
+table.sortable th:hover { background: #ddd; }
+td.number { text-align: right; }
+td.heat { text-align: center; min-width: 3em; }
+.summary td:first-child { font-weight: bold; }
+nav li { margin: 0.1em 0; }
+"#;
+
+static SORT_SCRIPT: &str = r#"
+document.querySelectorAll("table.sortable th").forEach(th => th.addEventListener("click", () => {
+  const body = th.closest("table").tBodies[0];
+  const i = Array.from(th.parentNode.children).indexOf(th);
+  const ascending = th.dataset.order !== "asc";
+  th.dataset.order = ascending ? "asc" : "desc";
+  const value = row => row.cells[i].textContent.trim();
+  Array.from(body.rows)
+    .sort((a, b) => {
+      const [x, y] = [value(a), value(b)];
+      const order = isNaN(x) || isNaN(y) ? x.localeCompare(y) : x - y;
+      return ascending ? order : -order;
+    })
+    .forEach(row => body.appendChild(row));
+}));
+"#;
+
+/// A single target species row of a magpie output table.
+struct Target {
+    common_name: String,
+    scientific_name: String,
+    percent: f64,
+    checklists: i64,
+    months: (u32, u32),
+}
+
+impl Target {
+    /// Identifies the species by its scientific name, or by its common name if it has none.
+    fn key(&self) -> &str {
+        if self.scientific_name.is_empty() {
+            &self.common_name
+        } else {
+            &self.scientific_name
+        }
+    }
+}
+
+/// Escapes text for inclusion in HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Names a location from its most to least specific level, skipping repeated names.
+fn location_name(levels: &[&String]) -> String {
+    levels
+        .iter()
+        .filter(|l| !l.is_empty())
+        .dedup()
+        .join(", ")
+}
+
+/// Groups the rows of a magpie output table by location, keeping locations in order of first appearance.
+/// Locations with no targets are kept with an empty list of targets.
+fn group_targets(df: &DataFrame) -> PolarsResult<Vec<(String, Vec<Target>)>> {
+    let hotspots = string_column(df, HOTSPOT)?;
+    let sub_regions = string_column(df, SUB_REGION)?;
+    let regions = string_column(df, REGION)?;
+    let countries = string_column(df, COUNTRY)?;
+    let common_names = string_column(df, COMMON_NAME)?;
+    let scientific_names = string_column(df, SCIENTIFIC_NAME)?;
+    let percent = df.column(PERCENT)?.cast(&DataType::Float64)?;
+    let checklists = df.column(CHECKLISTS)?.cast(&DataType::Int64)?;
+    let starts = df.column(START_MONTH)?.cast(&DataType::UInt32)?;
+    let ends = df.column(END_MONTH)?.cast(&DataType::UInt32)?;
+
+    let mut locations: Vec<(String, Vec<Target>)> = vec![];
+    let mut location_index = HashMap::new();
+    for (i, (((p, n), s), e)) in percent
+        .f64()?
+        .into_iter()
+        .zip(checklists.i64()?)
+        .zip(starts.u32()?)
+        .zip(ends.u32()?)
+        .enumerate()
+    {
+        let name = location_name(&[&hotspots[i], &sub_regions[i], &regions[i], &countries[i]]);
+        let index = *location_index.entry(name.clone()).or_insert_with(|| {
+            locations.push((name, vec![]));
+            locations.len() - 1
+        });
+        if common_names[i].is_empty() && scientific_names[i].is_empty() {
+            continue;
+        }
+        locations[index].1.push(Target {
+            common_name: common_names[i].clone(),
+            scientific_name: scientific_names[i].clone(),
+            percent: p.unwrap_or(0.0),
+            checklists: n.unwrap_or(0),
+            months: (s.unwrap_or(1), e.unwrap_or(12)),
+        });
+    }
+    Ok(locations)
+}
+
+/// Renders a table of summary counts for the whole output.
+fn summary(html: &mut String, locations: &[(String, Vec<Target>)]) -> fmt::Result {
+    let targets: usize = locations.iter().map(|(_, t)| t.len()).sum();
+    let species = locations
+        .iter()
+        .flat_map(|(_, t)| t.iter().map(|t| &t.scientific_name))
+        .unique()
+        .count();
+    let month_ranges = locations
+        .iter()
+        .flat_map(|(_, t)| t.iter().map(|t| t.months))
+        .unique()
+        .count();
+    html.push_str("<h2>Summary</h2>\n<table class=\"summary\">\n");
+    for (label, count) in [
+        ("Locations", locations.len()),
+        ("Species", species),
+        ("Month ranges", month_ranges),
+        ("Target rows", targets),
+    ] {
+        writeln!(html, "<tr><td>{}</td><td class=\"number\">{}</td></tr>", label, count)?;
+    }
+    html.push_str("</table>\n");
+    Ok(())
+}
+
+/// Renders the ranking of locations by expected new species.
+fn ranking(html: &mut String, df: &DataFrame, threshold: f32) -> Result<(), Box<dyn Error>> {
+    let ranked = rank(df.clone(), threshold)?;
+    html.push_str("<h2>Locations</h2>\n<table class=\"sortable\">\n<thead><tr>");
+    for name in ranked.get_column_names() {
+        write!(html, "<th>{}</th>", escape(name))?;
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+    let columns = ranked
+        .get_columns()
+        .iter()
+        .map(|s| s.cast(&DataType::String))
+        .collect::<PolarsResult<Vec<_>>>()?;
+    for i in 0..ranked.height() {
+        html.push_str("<tr>");
+        for column in &columns {
+            let value = column.str()?.get(i).unwrap_or_default();
+            match value.parse::<f64>() {
+                Ok(number) if value.contains('.') => {
+                    write!(html, "<td class=\"number\">{:.2}</td>", number)?
+                }
+                Ok(_) => write!(html, "<td class=\"number\">{}</td>", value)?,
+                Err(_) => write!(html, "<td>{}</td>", escape(value))?,
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>\n");
+    Ok(())
+}
+
+/// Renders a species by month table with each cell shaded by frequency.
+fn heatmap(html: &mut String, targets: &[Target]) -> fmt::Result {
+    let month_ranges: BTreeSet<_> = targets.iter().map(|t| t.months).collect();
+    let frequencies: HashMap<_, _> = targets
+        .iter()
+        .map(|t| ((t.key(), t.months), t.percent))
+        .collect();
+    let species = targets
+        .iter()
+        .into_group_map_by(|t| t.key())
+        .into_iter()
+        .map(|(key, t)| {
+            let names = (t[0].common_name.as_str(), t[0].scientific_name.as_str());
+            (key, names, t.iter().map(|t| t.percent).fold(0.0, f64::max))
+        })
+        .sorted_by(|(_, _, a), (_, _, b)| b.total_cmp(a))
+        .map(|(key, names, _)| (key, names))
+        .collect::<Vec<_>>();
+
+    html.push_str("<table class=\"heatmap\">\n<thead><tr><th>Species</th>");
+    for &(s, e) in &month_ranges {
+        write!(html, "<th>{}</th>", month_label(s, e))?;
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+    for (key, (common_name, scientific_name)) in species {
+        write!(
+            html,
+            "<tr><td title=\"{}\">{}</td>",
+            escape(scientific_name),
+            escape(common_name)
+        )?;
+        for &months in &month_ranges {
+            match frequencies.get(&(key, months)) {
+                Some(p) => {
+                    write!(
+                        html,
+                        "<td class=\"heat\" style=\"background: rgba(33, 113, 181, {:.2})\">{:.0}</td>",
+                        (p / 100.0).clamp(0.05, 1.0),
+                        p
+                    )?;
+                }
+                None => html.push_str("<td class=\"heat\"></td>"),
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>\n");
+    Ok(())
+}
+
+/// Renders the sortable table of targets for a single location.
+fn target_table(html: &mut String, targets: &[Target]) -> fmt::Result {
+    html.push_str(
+        "<table class=\"sortable\">\n<thead><tr><th>Common name</th><th>Scientific name</th>\
+         <th>Months</th><th>Percent</th><th>Checklists</th></tr></thead>\n<tbody>\n",
+    );
+    for t in targets
+        .iter()
+        .sorted_by(|a, b| a.months.cmp(&b.months).then(b.percent.total_cmp(&a.percent)))
+    {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td><em>{}</em></td><td>{}</td><td class=\"number\">{:.2}</td><td class=\"number\">{}</td></tr>",
+            escape(&t.common_name),
+            escape(&t.scientific_name),
+            month_label(t.months.0, t.months.1),
+            t.percent,
+            t.checklists
+        )?;
+    }
+    html.push_str("</tbody>\n</table>\n");
+    Ok(())
+}
+
+/// Renders a magpie output table as a single self-contained HTML page.
+///
+/// The page has summary counts, a sortable ranking of all locations (see `rank`), and a section for each
+/// location with a sortable table of its targets. Locations scraped for more than one month
+/// range, as with `--all`, also get a species by month heatmap. All styles and scripts are
+/// inline, so the page can be opened offline and shared as a single file.
+pub(crate) fn render_html(
+    df: &DataFrame,
+    title: &str,
+    threshold: f32,
+) -> Result<String, Box<dyn Error>> {
+    let locations = group_targets(df)?;
+    let mut html = String::new();
+    write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{0}</title>\n<style>{1}</style>\n</head>\n<body>\n<h1>{0}</h1>\n",
+        escape(title),
+        STYLE
+    )?;
+    summary(&mut html, &locations)?;
+    ranking(&mut html, df, threshold)?;
+
+    html.push_str("<nav><ul>\n");
+    for (i, (name, targets)) in locations.iter().enumerate() {
+        writeln!(
+            html,
+            "<li><a href=\"#location-{}\">{}</a> ({})</li>",
+            i,
+            escape(name),
+            targets.len()
+        )?;
+    }
+    html.push_str("</ul></nav>\n");
+
+    for (i, (name, targets)) in locations.iter().enumerate() {
+        writeln!(html, "<h2 id=\"location-{}\">{}</h2>", i, escape(name))?;
+        if targets.iter().map(|t| t.months).unique().count() > 1 {
+            heatmap(&mut html, targets)?;
+        }
+        target_table(&mut html, targets)?;
+    }
+
+    write!(html, "<script>{}</script>\n</body>\n</html>\n", SORT_SCRIPT)?;
+    Ok(html)
+}
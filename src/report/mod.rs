@@ -0,0 +1,77 @@
+pub mod confidence;
+pub mod diff;
+pub mod find;
+pub mod html;
+pub mod plan;
+pub mod rank;
+
+use std::error::Error;
+use std::fs::File;
+use std::io;
+
+use polars::prelude::{CsvWriter, DataFrame, DataType, PolarsResult, SerWriter};
+
+use crate::target::{COUNTRY, END_MONTH, HOTSPOT, REGION, START_MONTH, SUB_REGION};
+
+static ABOVE_THRESHOLD: &str = "above threshold";
+static CHANGE: &str = "change";
+static CUMULATIVE_EXPECTED: &str = "cumulative expected species";
+static EXPECTED: &str = "expected new species";
+static LOWER_PERCENT: &str = "lower percent";
+static MARGINAL_GAIN: &str = "marginal gain";
+static NEW_PERCENT: &str = "new percent";
+static OLD_PERCENT: &str = "old percent";
+static PICK: &str = "pick";
+static RELIABLE: &str = "reliable";
+static STATUS: &str = "status";
+static TARGETS: &str = "targets";
+static TOP_SPECIES: &str = "top species";
+static UPPER_PERCENT: &str = "upper percent";
+static WEIGHTED_PERCENT: &str = "weighted percent";
+
+/// Returns the columns identifying a location and month range in a magpie output table.
+/// The hotspot column is only included if the output was scraped on the hotspot level.
+fn location_columns(df: &DataFrame) -> Vec<&'static str> {
+    let mut columns = vec![COUNTRY, REGION, SUB_REGION];
+    if df.get_column_index(HOTSPOT).is_some() {
+        columns.push(HOTSPOT);
+    }
+    columns.extend([START_MONTH, END_MONTH]);
+    columns
+}
+
+/// Writes a report table as csv, either to the given file or to standard output.
+pub(crate) fn write_table(df: &mut DataFrame, output: Option<&str>) -> Result<(), Box<dyn Error>> {
+    match output {
+        Some(filename) => {
+            let file = File::create(filename)?;
+            CsvWriter::new(&file).include_header(true).finish(df)?
+        }
+        None => CsvWriter::new(io::stdout())
+            .include_header(true)
+            .finish(df)?,
+    }
+    Ok(())
+}
+
+/// Returns the values of a column as strings, with nulls for missing values.
+pub(crate) fn strings(df: &DataFrame, name: &str) -> PolarsResult<Vec<Option<String>>> {
+    Ok(df
+        .column(name)?
+        .cast(&DataType::String)?
+        .str()?
+        .into_iter()
+        .map(|s| s.map(ToString::to_string))
+        .collect())
+}
+
+/// Returns the values of a column as strings, empty for missing values or if the column is missing.
+pub(crate) fn string_column(df: &DataFrame, name: &str) -> PolarsResult<Vec<String>> {
+    if df.get_column_index(name).is_none() {
+        return Ok(vec![String::new(); df.height()]);
+    }
+    Ok(strings(df, name)?
+        .into_iter()
+        .map(Option::unwrap_or_default)
+        .collect())
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use polars::prelude::*;
+
+use crate::report::{location_columns, CUMULATIVE_EXPECTED, MARGINAL_GAIN, PICK, TOP_SPECIES};
+use crate::target::{COMMON_NAME, PERCENT, SCIENTIFIC_NAME};
+
+/// Number of species listed as the main contributors to each pick.
+static TOP_SPECIES_COUNT: usize = 3;
+
+/// A location and month range that can be added to an itinerary, with the probability of
+/// seeing each of its target species.
+struct Candidate {
+    key: Vec<Option<String>>,
+    species: Vec<(usize, f64)>,
+}
+
+/// A candidate chosen by the planner along with the gain it added at the time it was chosen.
+struct Pick<'a> {
+    number: u32,
+    candidate: &'a Candidate,
+    gain: f64,
+    cumulative: f64,
+    top_species: Vec<(usize, f64)>,
+}
+
+/// Groups the rows of a magpie output table into one candidate per location and month range.
+/// Species are identified by scientific name, or common name if no scientific name is given.
+/// Rows without a species, for locations with no targets, add a candidate with no species.
+/// Returns the candidates and the names of all species in the table.
+fn make_candidates(df: &DataFrame) -> PolarsResult<(Vec<Candidate>, Vec<String>)> {
+    let key_columns = df
+        .columns(location_columns(df))?
+        .into_iter()
+        .map(|s| s.cast(&DataType::String))
+        .collect::<PolarsResult<Vec<_>>>()?;
+    let mut key_iters = key_columns
+        .iter()
+        .map(|s| s.str().map(|ca| ca.into_iter()))
+        .collect::<PolarsResult<Vec<_>>>()?;
+    let common_names = df.column(COMMON_NAME)?.str()?;
+    let scientific_names = df.column(SCIENTIFIC_NAME)?.str()?;
+    let percent = df.column(PERCENT)?.cast(&DataType::Float64)?;
+
+    let mut candidates: Vec<Candidate> = vec![];
+    let mut candidate_index = HashMap::new();
+    let mut species_names = vec![];
+    let mut species_index = HashMap::new();
+
+    for ((common, scientific), p) in common_names
+        .into_iter()
+        .zip(scientific_names)
+        .zip(percent.f64()?)
+    {
+        let key: Vec<_> = key_iters
+            .iter_mut()
+            .map(|iter| iter.next().flatten().map(ToString::to_string))
+            .collect();
+        let candidate = *candidate_index.entry(key.clone()).or_insert_with(|| {
+            candidates.push(Candidate {
+                key,
+                species: vec![],
+            });
+            candidates.len() - 1
+        });
+        if common.is_none() && scientific.is_none() {
+            continue;
+        }
+        let name = scientific
+            .filter(|s| !s.is_empty())
+            .or(common)
+            .unwrap_or_default()
+            .to_string();
+        let species = *species_index.entry(name.clone()).or_insert_with(|| {
+            species_names.push(common.unwrap_or(&name).to_string());
+            species_names.len() - 1
+        });
+        let probability = (p.unwrap_or(0.0) / 100.0).clamp(0.0, 1.0);
+        candidates[candidate].species.push((species, probability));
+    }
+    Ok((candidates, species_names))
+}
+
+/// Greedily chooses up to `budget` candidates maximizing the expected number of distinct species.
+///
+/// A species with probability `p_i` at each chosen location is seen with probability
+/// `1 - Π(1 - p_i)`, so a species shared by two picks is not counted twice. Each step adds the
+/// candidate with the largest gain in expected species given the picks already made. Since the
+/// objective is submodular, the greedy choice is within `1 - 1/e` of the best possible itinerary.
+fn greedy<'a>(candidates: &[&'a Candidate], species_count: usize, budget: usize) -> Vec<Pick<'a>> {
+    let mut missed = vec![1.0; species_count];
+    let mut available: Vec<_> = candidates.to_vec();
+    let mut picks = vec![];
+    let mut cumulative = 0.0;
+
+    while picks.len() < budget {
+        let Some((position, gain)) = available
+            .iter()
+            .map(|c| c.species.iter().map(|&(s, p)| missed[s] * p).sum::<f64>())
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        else {
+            break;
+        };
+        if gain <= 0.0 {
+            break;
+        }
+
+        let candidate = available.swap_remove(position);
+        let top_species = candidate
+            .species
+            .iter()
+            .map(|&(s, p)| (s, missed[s] * p))
+            .filter(|&(_, gain)| gain > 0.0)
+            .sorted_by(|(_, a), (_, b)| b.total_cmp(a))
+            .take(TOP_SPECIES_COUNT)
+            .collect();
+        candidate
+            .species
+            .iter()
+            .for_each(|&(s, p)| missed[s] *= 1.0 - p);
+        cumulative += gain;
+        picks.push(Pick {
+            number: picks.len() as u32 + 1,
+            candidate,
+            gain,
+            cumulative,
+            top_species,
+        });
+    }
+    picks
+}
+
+/// Plans an itinerary of at most `budget` locations and month ranges from a magpie output table
+/// that maximizes the expected number of distinct new species.
+///
+/// If `per_month` is set, a separate itinerary of `budget` locations is made for each month
+/// range in the table. Each pick is listed with its marginal gain, the running total of expected
+/// new species, and the species contributing most to its gain.
+pub(crate) fn plan(df: DataFrame, budget: usize, per_month: bool) -> PolarsResult<DataFrame> {
+    let (candidates, species_names) = make_candidates(&df)?;
+    let month_groups: Vec<Vec<&Candidate>> = if per_month {
+        candidates
+            .iter()
+            .into_group_map_by(|c| c.key[c.key.len() - 2..].to_vec())
+            .into_iter()
+            .sorted_by_key(|(months, _)| {
+                months
+                    .iter()
+                    .map(|m| m.as_deref().and_then(|m| m.parse::<u8>().ok()))
+                    .collect::<Vec<_>>()
+            })
+            .map(|(_, group)| group)
+            .collect()
+    } else {
+        vec![candidates.iter().collect()]
+    };
+    let picks: Vec<_> = month_groups
+        .iter()
+        .flat_map(|group| greedy(group, species_names.len(), budget))
+        .collect();
+
+    let mut columns = vec![Series::new(
+        PICK,
+        picks.iter().map(|p| p.number).collect::<Vec<_>>(),
+    )];
+    columns.extend(location_columns(&df).iter().enumerate().map(|(i, &name)| {
+        Series::new(
+            name,
+            picks
+                .iter()
+                .map(|p| p.candidate.key[i].clone())
+                .collect::<Vec<_>>(),
+        )
+    }));
+    columns.push(Series::new(
+        MARGINAL_GAIN,
+        picks.iter().map(|p| p.gain).collect::<Vec<_>>(),
+    ));
+    columns.push(Series::new(
+        CUMULATIVE_EXPECTED,
+        picks.iter().map(|p| p.cumulative).collect::<Vec<_>>(),
+    ));
+    columns.push(Series::new(
+        TOP_SPECIES,
+        picks
+            .iter()
+            .map(|p| {
+                p.top_species
+                    .iter()
+                    .map(|&(s, gain)| format!("{} ({:.2})", species_names[s], gain))
+                    .join("; ")
+            })
+            .collect::<Vec<_>>(),
+    ));
+    DataFrame::new(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str, species: &[(usize, f64)]) -> Candidate {
+        Candidate {
+            key: vec![Some(name.to_string())],
+            species: species.to_vec(),
+        }
+    }
+
+    #[test]
+    fn greedy_does_not_count_shared_species_twice() {
+        let shared = candidate("shared", &[(0, 0.9), (1, 0.5)]);
+        let similar = candidate("similar", &[(0, 0.9), (1, 0.4)]);
+        let other = candidate("other", &[(2, 0.8)]);
+        let candidates = [&shared, &similar, &other];
+
+        let picks = greedy(&candidates, 3, 2);
+        assert_eq!(picks.len(), 2);
+        assert_eq!(picks[0].candidate.key, shared.key);
+        assert!((picks[0].gain - 1.4).abs() < 1e-9);
+        // After the first pick, the similar candidate would only add 0.09 + 0.2.
+        assert_eq!(picks[1].candidate.key, other.key);
+        assert!((picks[1].cumulative - 2.2).abs() < 1e-9);
+        assert_eq!(picks[1].number, 2);
+    }
+
+    #[test]
+    fn greedy_stops_when_nothing_is_gained() {
+        let seen = candidate("seen", &[(0, 1.0)]);
+        let again = candidate("again", &[(0, 0.7)]);
+        let empty = candidate("empty", &[]);
+        let candidates = [&seen, &again, &empty];
+
+        let picks = greedy(&candidates, 1, 5);
+        assert_eq!(picks.len(), 1);
+        assert_eq!(picks[0].candidate.key, seen.key);
+        assert_eq!(picks[0].top_species, vec![(0, 1.0)]);
+    }
+}
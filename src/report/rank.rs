@@ -0,0 +1,35 @@
+use polars::prelude::*;
+
+use crate::report::{location_columns, ABOVE_THRESHOLD, EXPECTED, TARGETS};
+use crate::target::{COMMON_NAME, PERCENT};
+
+/// Ranks every location and month range in a magpie output table by the number of new species
+/// a visit can be expected to produce.
+///
+/// For each location and month range the table contains:
+/// - targets: The number of target species. Locations with no targets are ranked with zero.
+/// - above threshold: The number of target species reported on at least `threshold` percent of checklists.
+/// - expected new species: The sum of all target frequencies, treating each percent as a probability.
+///
+/// Rows are sorted by expected new species, with ties broken by the number of targets.
+pub(crate) fn rank(df: DataFrame, threshold: f32) -> PolarsResult<DataFrame> {
+    let group_columns: Vec<_> = location_columns(&df).into_iter().map(col).collect();
+    df.lazy()
+        .group_by(group_columns)
+        .agg([
+            col(COMMON_NAME).count().cast(DataType::UInt32).alias(TARGETS),
+            col(PERCENT)
+                .gt_eq(lit(threshold))
+                .cast(DataType::UInt32)
+                .sum()
+                .alias(ABOVE_THRESHOLD),
+            (col(PERCENT).cast(DataType::Float64) / lit(100.0))
+                .sum()
+                .alias(EXPECTED),
+        ])
+        .sort(
+            [EXPECTED, TARGETS],
+            SortMultipleOptions::default().with_order_descending(true),
+        )
+        .collect()
+}
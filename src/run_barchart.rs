@@ -0,0 +1,79 @@
+use std::error::Error;
+
+use clap::ArgMatches;
+use tracing::{info, warn};
+
+use crate::error::MagpieError;
+use crate::loc::{check_columns, load_codes, load_data};
+use crate::login;
+use crate::parse::MagpieParse;
+use crate::sink::{PageWriter, Sink};
+use crate::sqlite::is_sqlite;
+use crate::target::scrape_params::LocationLevel;
+use crate::target::{required_columns, BarChartScraper};
+
+/// Scrapes the weekly frequency of every species at each location from eBird's bar chart data,
+/// writing one row per species, location, and week in the same location columns as the targets
+/// output.
+pub(crate) fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let years = matches.get_barchart_years()?;
+    let output_file = matches.get_output_file()?;
+    if is_sqlite(output_file) {
+        return Err(MagpieError::InvalidArgument(
+            "barchart writes csv output, so SQLite output is not supported.".to_string(),
+        )
+        .into());
+    }
+    let loc_data = match matches.get_loc_codes() {
+        Some(codes) => {
+            let (region_file, hotspot_file) = matches.get_reference_files();
+            load_codes(&codes, region_file, hotspot_file)?
+        }
+        None => {
+            let (loc_file, list_level) = matches.get_loc_data();
+            vec![(list_level, load_data(loc_file)?)]
+        }
+    };
+    for (list_level, loc_df) in &loc_data {
+        let columns = required_columns(*list_level, None);
+        check_columns(loc_df, matches.get_loc_file(list_level), &columns)?;
+    }
+
+    let client = login::login()?.async_client()?;
+    let scrapers: Vec<_> = loc_data
+        .into_iter()
+        .map(|(list_level, loc_df)| {
+            BarChartScraper::new(client.clone(), list_level, loc_df, years)
+                .with_rate_limit(matches.get_rate_limit())
+                .with_concurrency(matches.get_concurrency())
+        })
+        .collect();
+
+    let writer = PageWriter::spawn(Sink::Csv {
+        file: output_file.to_string(),
+        hotspot: scrapers
+            .iter()
+            .any(|s| s.location_level == LocationLevel::Hotspot),
+        confidence: None,
+        existing: None,
+    });
+    let scraped = scrapers
+        .iter()
+        .enumerate()
+        .map(|(run, scraper)| scraper.stream_charts(|df| writer.send(run, df)))
+        .collect::<Result<Vec<_>, _>>();
+    writer.finish()?;
+
+    let failed = scraped?.concat();
+    if failed.is_empty() {
+        info!("Wrote bar charts of every location to {}", output_file);
+    } else {
+        warn!(
+            locations = %failed.join(", "),
+            "Gave up on {} bar charts, they are missing from {}",
+            failed.len(),
+            output_file
+        );
+    }
+    Ok(())
+}
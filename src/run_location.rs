@@ -1,44 +1,39 @@
 use std::error::Error;
-use std::fs::File;
 use std::time::Instant;
 
-use indicatif::ParallelProgressIterator;
-use polars::prelude::{CsvWriter, DataFrame, SerWriter};
+use indicatif::{ParallelProgressIterator, ProgressStyle};
 use rayon::prelude::*;
 use reqwest::blocking::Client;
+use tracing::info;
 
+use crate::doctor;
 use crate::location::df::{hotspot_to_df, sub_region_to_df};
 use crate::location::hotspot::get_hotspots;
 use crate::location::regions::{get_countries, get_regions, get_sub_regions};
+use crate::logging::progress_bar;
+use crate::report::write_table;
 use crate::target::print_hms;
 
-fn write_csv(df: &mut DataFrame, filename: &str) -> Result<(), Box<dyn Error>> {
-    let file = File::create(filename)?;
-    CsvWriter::new(&file)
-        .include_header(true)
-        .finish(df)
-        .map_err(|e| e.into())
-}
-
 pub fn run() -> Result<(), Box<dyn Error>> {
     let client = Client::builder().cookie_store(true).build()?;
+    doctor::check_locations(&client)?;
 
     let start = Instant::now();
     let countries = get_countries(&client)?;
 
     let regions: Vec<_> = countries
         .par_iter()
-        .progress()
+        .progress_with(progress_bar(countries.len(), ProgressStyle::default_bar()))
         .flat_map(|c| get_regions(&client, c, 1))
         .collect();
-    println!("Number of regions: {}", regions.len());
+    info!("Number of regions: {}", regions.len());
 
     let sub_regions: Vec<_> = regions
         .par_iter()
-        .progress()
+        .progress_with(progress_bar(regions.len(), ProgressStyle::default_bar()))
         .flat_map(|r| get_sub_regions(&client, r, 1))
         .collect();
-    println!("Number of sub-regions: {}", sub_regions.len());
+    info!("Number of sub-regions: {}", sub_regions.len());
 
     let mut sub_region_df = sub_region_to_df(&sub_regions)?;
     print_hms(&start);
@@ -46,14 +41,17 @@ pub fn run() -> Result<(), Box<dyn Error>> {
     let hotspot_start = Instant::now();
     let hotspots: Vec<_> = sub_regions
         .par_iter()
-        .progress()
+        .progress_with(progress_bar(
+            sub_regions.len(),
+            ProgressStyle::default_bar(),
+        ))
         .flat_map(|s| get_hotspots(&client, s, 1))
         .collect();
-    println!("Number of hotspots: {}", hotspots.len());
+    info!("Number of hotspots: {}", hotspots.len());
     let mut hotspot_df = hotspot_to_df(&hotspots)?;
     print_hms(&hotspot_start);
 
-    write_csv(&mut sub_region_df, "regions_pl.csv")?;
-    write_csv(&mut hotspot_df, "hotspots_pl.csv")?;
+    write_table(&mut sub_region_df, Some("regions_pl.csv"))?;
+    write_table(&mut hotspot_df, Some("hotspots_pl.csv"))?;
     Ok(())
 }
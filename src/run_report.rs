@@ -0,0 +1,126 @@
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+
+use clap::ArgMatches;
+
+use crate::error::MagpieError;
+use crate::loc::load_data;
+use crate::report::confidence::add_confidence;
+use crate::report::diff::diff;
+use crate::report::find::find_species;
+use crate::report::html::render_html;
+use crate::report::plan::plan;
+use crate::report::rank::rank;
+use crate::report::write_table;
+use crate::target::outcome::load_failures;
+
+pub(crate) fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    match matches.subcommand() {
+        Some(("rank", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Missing input file.");
+            let threshold = *sub_matches
+                .get_one::<f32>("threshold")
+                .expect("Missing threshold.");
+            let mut ranking = rank(load_data(input)?, threshold)?;
+            write_table(
+                &mut ranking,
+                sub_matches.get_one::<String>("output").map(|s| s.as_str()),
+            )
+        }
+        Some(("confidence", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Missing input file.");
+            let z = *sub_matches.get_one::<f64>("z").expect("Missing z.");
+            let max_width = *sub_matches
+                .get_one::<f64>("max_width")
+                .expect("Missing max width.");
+            let mut intervals = add_confidence(load_data(input)?, z, max_width)?;
+            write_table(
+                &mut intervals,
+                sub_matches.get_one::<String>("output").map(|s| s.as_str()),
+            )
+        }
+        Some(("html", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Missing input file.");
+            let title = sub_matches
+                .get_one::<String>("title")
+                .expect("Missing title.");
+            let threshold = *sub_matches
+                .get_one::<f32>("threshold")
+                .expect("Missing threshold.");
+            let html = render_html(&load_data(input)?, title, threshold)?;
+            match sub_matches.get_one::<String>("output") {
+                Some(filename) => fs::write(filename, html)?,
+                None => io::stdout().write_all(html.as_bytes())?,
+            }
+            Ok(())
+        }
+        Some(("plan", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Missing input file.");
+            let budget = *sub_matches
+                .get_one::<usize>("locations")
+                .expect("Missing number of locations.");
+            let mut itinerary = plan(load_data(input)?, budget, sub_matches.get_flag("per_month"))?;
+            write_table(
+                &mut itinerary,
+                sub_matches.get_one::<String>("output").map(|s| s.as_str()),
+            )
+        }
+        _ => Err(MagpieError::InvalidArgument("Invalid report type.".to_string()).into()),
+    }
+}
+
+pub(crate) fn run_where(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let species = matches
+        .get_one::<String>("species")
+        .expect("Missing species.");
+    let input = matches
+        .get_one::<String>("input")
+        .expect("Missing input file.");
+    let prior_checklists = *matches
+        .get_one::<f64>("prior_checklists")
+        .expect("Missing prior checklists.");
+    let limit = *matches
+        .get_one::<u32>("limit")
+        .expect("Missing limit.");
+
+    let mut locations = find_species(load_data(input)?, species, prior_checklists, limit)?;
+    if locations.height() == 0 {
+        return Err(format!("No targets named {} in {}", species, input).into());
+    }
+    write_table(
+        &mut locations,
+        matches.get_one::<String>("output").map(|s| s.as_str()),
+    )
+}
+
+pub(crate) fn run_diff(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let old = matches
+        .get_one::<String>("old")
+        .expect("Missing old output file.");
+    let new = matches
+        .get_one::<String>("new")
+        .expect("Missing new output file.");
+    let threshold = *matches
+        .get_one::<f64>("threshold")
+        .expect("Missing threshold.");
+
+    let mut changes = diff(
+        load_data(old)?,
+        load_data(new)?,
+        load_failures(new)?.as_ref(),
+        threshold,
+    )?;
+    write_table(
+        &mut changes,
+        matches.get_one::<String>("output").map(|s| s.as_str()),
+    )
+}
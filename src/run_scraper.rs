@@ -1,34 +1,212 @@
 use std::error::Error;
-use std::fs::File;
+use std::path::Path;
+use std::time::Duration;
 
 use clap::ArgMatches;
-use polars::prelude::*;
+use polars::functions::concat_df_diagonal;
+use reqwest::Client;
+use tracing::info;
 
-use crate::loc::load_data;
-use crate::login;
+use crate::doctor;
+use crate::error::MagpieError;
+use crate::loc::{check_columns, load_codes, load_data};
+use crate::login::{self, Session};
+use crate::notify::notify;
 use crate::parse::MagpieParse;
-use crate::target::Scraper;
+use crate::report::diff::new_targets;
+use crate::report::write_table;
+use crate::sink::{PageWriter, Sink};
+use crate::sqlite::{is_sqlite, RunParams};
+use crate::target::outcome::{drop_pages, failed_pages, failures_file, merge_outcomes};
+use crate::target::scrape_params::LocationLevel;
+use crate::target::{format_hms, required_columns, Scraper};
+
+static DRY_RUN_SAMPLES: usize = 5;
+
+/// Prints the number of requests a run would send, a sample of their URLs, and the estimated run time.
+fn dry_run(scrapers: &[Scraper]) -> Result<(), Box<dyn Error>> {
+    let mut requests = 0;
+    let mut sample_urls = vec![];
+    let mut eta = Duration::ZERO;
+    for scraper in scrapers {
+        let plan = scraper.dry_run(DRY_RUN_SAMPLES)?;
+        requests += plan.requests;
+        sample_urls.extend(plan.sample_urls);
+        eta += plan.eta;
+    }
+    println!("Requests: {}", requests);
+    println!("Sample URLs:");
+    for url in sample_urls.iter().take(DRY_RUN_SAMPLES) {
+        println!("  {}", url);
+    }
+    println!("Estimated time: {}", format_hms(&eta));
+    Ok(())
+}
 
 pub(crate) fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
-    let (loc_file, list_level) = matches.get_loc_data();
-    let loc_df = load_data(loc_file);
-    let list_type = matches.get_list_type();
-    let date_range = matches.get_date_range();
-    let time_range = matches.get_time_range();
-    let output_file = matches.get_output_file();
+    run_with(matches, login::login)
+}
+
+/// Runs a scrape, logging in with `login` unless it is a dry run.
+pub(crate) fn run_with<L>(matches: &ArgMatches, login: L) -> Result<(), Box<dyn Error>>
+where
+    L: FnOnce() -> Result<Session, Box<dyn Error>>,
+{
+    let loc_data = match matches.get_loc_codes() {
+        Some(codes) => {
+            let (region_file, hotspot_file) = matches.get_reference_files();
+            load_codes(&codes, region_file, hotspot_file)?
+        }
+        None => {
+            let (loc_file, list_level) = matches.get_loc_data();
+            vec![(list_level, load_data(loc_file)?)]
+        }
+    };
+    let list_types = loc_data
+        .iter()
+        .map(|(list_level, _)| matches.get_list_type(list_level))
+        .collect::<Result<Vec<_>, _>>()?;
+    for ((list_level, loc_df), list_type) in loc_data.iter().zip(&list_types) {
+        let columns = required_columns(*list_level, Some(list_type));
+        check_columns(loc_df, matches.get_loc_file(list_level), &columns)?;
+    }
+    let date_range = matches.get_date_range()?;
+    let time_range = matches.get_time_range()?;
+    let retry = match matches.get_retry_file() {
+        Some(retry_file) => {
+            let old_outcomes = load_data(retry_file)?;
+            let retry_pages = failed_pages(&old_outcomes)?;
+            if retry_pages.is_empty() {
+                info!("No failed pages to retry in {}", retry_file);
+                return Ok(());
+            }
+            Some((old_outcomes, retry_pages))
+        }
+        None => None,
+    };
+    let retry_pages = retry.as_ref().map(|(_, pages)| pages.clone());
+    if matches.get_dry_run() {
+        let client = Client::builder().cookie_store(true).build()?;
+        let scrapers: Vec<_> = loc_data
+            .into_iter()
+            .zip(list_types)
+            .map(|((list_level, loc_df), list_type)| {
+                Scraper::new(
+                    client.clone(),
+                    date_range.clone(),
+                    list_level,
+                    list_type,
+                    loc_df,
+                    time_range.clone(),
+                )
+                .with_rate_limit(matches.get_rate_limit())
+                .with_concurrency(matches.get_concurrency())
+                .with_retry_pages(retry_pages.clone())
+            })
+            .collect();
+        return dry_run(&scrapers);
+    }
+    let output_file = matches.get_output_file()?;
+    if is_sqlite(output_file) && (matches.get_wide_output() || matches.get_confidence().is_some())
+    {
+        return Err(MagpieError::InvalidArgument(
+            "--wide and --confidence are not supported for SQLite output.".to_string(),
+        )
+        .into());
+    }
+
+    let notifications = matches.get_notifications();
+    if notifications.is_some() && (is_sqlite(output_file) || matches.get_wide_output()) {
+        return Err(MagpieError::InvalidArgument(
+            "Notifications need long csv output, so --wide and SQLite output are not supported."
+                .to_string(),
+        )
+        .into());
+    }
+    let previous = match &notifications {
+        Some(_) if Path::new(output_file).exists() => Some(load_data(output_file)?),
+        Some(_) => {
+            info!("No earlier output in {}, so no targets are new yet", output_file);
+            None
+        }
+        None => None,
+    };
 
-    let client = login::login()?;
+    let session = login()?;
+    if !matches.get_skip_check() {
+        doctor::check_targets(&session.client)?;
+    }
+    let client = session.async_client()?;
 
-    let scraper = Scraper::new(
-        client, date_range, list_level, list_type, loc_df, time_range,
-    );
+    let (run_params, scrapers): (Vec<_>, Vec<_>) = loc_data
+        .into_iter()
+        .zip(list_types)
+        .map(|((list_level, loc_df), list_type)| {
+            let run_params = RunParams::new(&list_level, &list_type, &date_range);
+            let scraper = Scraper::new(
+                client.clone(),
+                date_range.clone(),
+                list_level,
+                list_type,
+                loc_df,
+                time_range.clone(),
+            )
+            .with_rate_limit(matches.get_rate_limit())
+            .with_concurrency(matches.get_concurrency())
+            .with_retry_pages(retry_pages.clone());
+            (run_params, scraper)
+        })
+        .unzip();
 
-    let mut output = scraper.scrape_pages()?;
+    let sink = if is_sqlite(output_file) {
+        Sink::Sqlite {
+            file: output_file.to_string(),
+            runs: run_params,
+        }
+    } else if matches.get_wide_output() {
+        Sink::Wide {
+            file: output_file.to_string(),
+        }
+    } else {
+        let existing = match &retry {
+            Some((old_outcomes, retry_pages)) => Some(drop_pages(
+                &load_data(output_file)?,
+                old_outcomes,
+                retry_pages,
+            )?),
+            None => None,
+        };
+        Sink::Csv {
+            file: output_file.to_string(),
+            hotspot: scrapers
+                .iter()
+                .any(|s| s.location_level == LocationLevel::Hotspot),
+            confidence: matches.get_confidence(),
+            existing,
+        }
+    };
+    let writer = PageWriter::spawn(sink);
+    let scraped = scrapers
+        .iter()
+        .enumerate()
+        .map(|(run, scraper)| scraper.stream_pages(|df| writer.send(run, df)))
+        .collect::<Result<Vec<_>, _>>();
+    writer.finish()?;
 
-    let file = File::create(output_file)?;
-    CsvWriter::new(&file)
-        .include_header(true)
-        .finish(&mut output)?;
+    let mut outcomes = concat_df_diagonal(&scraped?)?;
+    if let Some((old_outcomes, _)) = &retry {
+        outcomes = merge_outcomes(old_outcomes, &outcomes)?;
+    }
+    write_table(&mut outcomes, Some(&failures_file(output_file)))?;
 
+    if let (Some(notifications), Some(previous)) = (notifications, previous) {
+        let targets = new_targets(
+            previous,
+            load_data(output_file)?,
+            Some(&outcomes),
+            notifications.threshold,
+        )?;
+        notify(&notifications, output_file, &targets)?;
+    }
     Ok(())
 }
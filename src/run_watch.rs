@@ -0,0 +1,131 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Instant;
+
+use chrono::Utc;
+use clap::ArgMatches;
+use tracing::{info, warn};
+
+use crate::error::MagpieError;
+use crate::loc::load_data;
+use crate::login::{self, Credentials};
+use crate::parse::MagpieParse;
+use crate::report::diff::{count_changes, diff};
+use crate::report::write_table;
+use crate::run_scraper;
+use crate::sqlite::is_sqlite;
+use crate::target::format_hms;
+use crate::target::outcome::load_failures;
+
+/// Format of the UTC timestamp in snapshot names. Sorting the names sorts the snapshots by time,
+/// also across daylight saving changes.
+static STAMP_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// Returns the stem of the output file, used to name its snapshots.
+fn output_stem(output_file: &str) -> String {
+    Path::new(output_file)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Returns the latest snapshot of the output file in the snapshot directory, if there is one.
+/// Snapshots are named for the output file and the time of the run, e.g. `norway.20240601T080000.csv`.
+fn latest_snapshot(dir: &str, output_file: &str) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let prefix = format!("{}.", output_stem(output_file));
+    let mut snapshots = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_snapshot = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|name| name.strip_suffix(".csv"))
+            .is_some_and(|stamp| {
+                stamp.len() == 15 && stamp.chars().all(|c| c.is_ascii_digit() || c == 'T')
+            });
+        if is_snapshot {
+            snapshots.push(path);
+        }
+    }
+    Ok(snapshots.into_iter().max())
+}
+
+/// Copies the output of a run into the snapshot directory and reports the changes since the
+/// previous snapshot. The changes are written next to the snapshot, e.g.
+/// `norway.20240608T080000.changes.csv`.
+fn record_snapshot(output_file: &str, dir: &str, threshold: f64) -> Result<(), Box<dyn Error>> {
+    let previous = latest_snapshot(dir, output_file)?;
+    let stamp = Utc::now().format(STAMP_FORMAT);
+    let snapshot = Path::new(dir).join(format!("{}.{}.csv", output_stem(output_file), stamp));
+    fs::copy(output_file, &snapshot)?;
+    info!("Saved snapshot {}", snapshot.display());
+
+    let Some(previous) = previous else {
+        println!("First snapshot saved, changes are reported from the next run.");
+        return Ok(());
+    };
+    let mut changes = diff(
+        load_data(&previous.to_string_lossy())?,
+        load_data(&snapshot.to_string_lossy())?,
+        load_failures(output_file)?.as_ref(),
+        threshold,
+    )?;
+    let changes_file = snapshot.with_extension("changes.csv");
+    write_table(&mut changes, Some(&changes_file.to_string_lossy()))?;
+
+    let (new, lost, changed) = count_changes(&changes)?;
+    println!(
+        "Since {}: {} new targets, {} lost, {} frequency changes of at least {} points. Details in {}.",
+        previous.display(),
+        new,
+        lost,
+        changed,
+        threshold,
+        changes_file.display()
+    );
+    Ok(())
+}
+
+/// Scrapes the targets of a profile or command line on a schedule, keeping a snapshot of each run
+/// and reporting new targets and frequency changes since the previous snapshot.
+///
+/// Credentials are asked for once and used to log in again before every run, so the session
+/// never expires between runs. The first run fails the command if it fails; later failures are
+/// logged and the next run goes ahead on schedule.
+pub(crate) fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let output_file = matches.get_output_file()?;
+    if is_sqlite(output_file) || matches.get_wide_output() {
+        return Err(MagpieError::InvalidArgument(
+            "watch needs long csv output, so --wide and SQLite output are not supported."
+                .to_string(),
+        )
+        .into());
+    }
+    let every = matches.get_every();
+    let dir = matches.get_snapshot_dir();
+    let threshold = *matches
+        .get_one::<f64>("threshold")
+        .expect("Missing threshold.");
+    fs::create_dir_all(dir)?;
+
+    let credentials = Credentials::prompt()?;
+    let mut first = true;
+    loop {
+        let start = Instant::now();
+        let result = run_scraper::run_with(matches, || login::login_with(&credentials))
+            .and_then(|()| record_snapshot(output_file, dir, threshold));
+        match result {
+            Err(e) if first => return Err(e),
+            Err(e) => warn!(error = %e, "Scheduled run failed"),
+            Ok(()) => {}
+        }
+        first = false;
+
+        let wait = every.saturating_sub(start.elapsed());
+        info!("Next run in {}", format_hms(&wait));
+        thread::sleep(wait);
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::OnceLock;
+
+use scraper::Selector;
+use toml::{Table, Value};
+use tracing::info;
+
+use crate::error::MagpieError;
+use crate::location::selectors::DEFAULTS as LOCATION_DEFAULTS;
+use crate::target::selectors::DEFAULTS as TARGET_DEFAULTS;
+
+static LOCATION_GROUP: &str = "location";
+static TARGET_GROUP: &str = "target";
+
+static OVERRIDES: OnceLock<HashMap<(String, String), String>> = OnceLock::new();
+
+/// Returns the names of the selectors in a group, or None if there is no such group.
+fn group_names(group: &str) -> Option<Vec<&'static str>> {
+    let defaults = if group == TARGET_GROUP {
+        TARGET_DEFAULTS
+    } else if group == LOCATION_GROUP {
+        LOCATION_DEFAULTS
+    } else {
+        return None;
+    };
+    Some(defaults.iter().map(|(name, _)| *name).collect())
+}
+
+/// Checks a single override, returning a message describing the problem if it is invalid.
+fn validate(group: &str, name: &str, value: &Value) -> Result<String, String> {
+    let names = group_names(group).ok_or_else(|| {
+        format!(
+            "unknown group [{}], expected [{}] or [{}]",
+            group, TARGET_GROUP, LOCATION_GROUP
+        )
+    })?;
+    if !names.contains(&name) {
+        return Err(format!(
+            "unknown selector {}.{}, expected one of {}",
+            group,
+            name,
+            names.join(", ")
+        ));
+    }
+    let selector = value
+        .as_str()
+        .ok_or_else(|| format!("selector {}.{} is not a string", group, name))?;
+    Selector::parse(selector)
+        .map(|_| selector.to_string())
+        .map_err(|_| format!("selector {}.{} = '{}' does not parse", group, name, selector))
+}
+
+/// Loads selector overrides from a TOML file with one table per group of selectors, e.g.
+///
+/// ```toml
+/// [target]
+/// rows = 'li[class="ResultsStats ResultsStats--action"]'
+///
+/// [location]
+/// leaderboard = 'div[class="LeaderBoard"]'
+/// ```
+///
+/// Every override is checked before any page is scraped: the group and selector must exist and
+/// the selector must parse. Overrides must be loaded before the selectors are first used.
+pub(crate) fn load(file: &str) -> Result<(), MagpieError> {
+    let config: Table = fs::read_to_string(file)
+        .map_err(|e| e.to_string())
+        .and_then(|s| s.parse().map_err(|e: toml::de::Error| e.message().to_string()))
+        .map_err(|message| MagpieError::LoadFile {
+            file: file.to_string(),
+            message,
+        })?;
+
+    let mut overrides = HashMap::new();
+    let mut errors = vec![];
+    for (group, selectors) in &config {
+        let Some(selectors) = selectors.as_table() else {
+            errors.push(format!("[{}] is not a table", group));
+            continue;
+        };
+        for (name, value) in selectors {
+            match validate(group, name, value) {
+                Ok(selector) => {
+                    overrides.insert((group.clone(), name.clone()), selector);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(MagpieError::InvalidArgument(format!(
+            "Invalid selector file {}: {}.",
+            file,
+            errors.join("; ")
+        )));
+    }
+
+    info!("Loaded {} selector overrides from {}", overrides.len(), file);
+    OVERRIDES.set(overrides).map_err(|_| {
+        MagpieError::InvalidArgument("Selector overrides were already loaded.".to_string())
+    })
+}
+
+/// Returns the override for a selector, if one was loaded.
+pub(crate) fn selector_override(group: &str, name: &str) -> Option<&'static str> {
+    OVERRIDES
+        .get()?
+        .get(&(group.to_string(), name.to_string()))
+        .map(String::as_str)
+}
@@ -0,0 +1,209 @@
+use std::error::Error;
+use std::fs::File;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use itertools::Itertools;
+use polars::functions::concat_df_diagonal;
+use polars::prelude::*;
+
+use crate::report::confidence::add_confidence;
+use crate::sqlite::{RunParams, SqliteWriter};
+use crate::target::{pivot_months, HOTSPOT, START_MONTH};
+
+/// Number of scraped pages that can wait for the writer before scraping threads block.
+static CHANNEL_CAPACITY: usize = 256;
+
+type WriterResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+/// Where scraped pages are written.
+///
+/// - Csv: Long output, appended page by page. Confidence intervals are added to each page as it
+///   is written. Rows kept from an earlier run are written first.
+/// - Wide: Wide output, which needs every page before it can be pivoted, so pages are only
+///   written once scraping is done.
+/// - Sqlite: Targets are inserted and committed page by page.
+pub(crate) enum Sink {
+    Csv {
+        file: String,
+        hotspot: bool,
+        confidence: Option<(f64, f64)>,
+        existing: Option<DataFrame>,
+    },
+    Wide {
+        file: String,
+    },
+    Sqlite {
+        file: String,
+        runs: Vec<RunParams>,
+    },
+}
+
+/// Writes pages to a sink from its own thread as they are scraped, so memory does not grow
+/// with the size of the run and finished pages are on disk while the run continues.
+pub(crate) struct PageWriter {
+    sender: SyncSender<(usize, DataFrame)>,
+    handle: JoinHandle<WriterResult>,
+}
+
+/// Puts the hotspot column, filled with nulls if the page has none, just before the month
+/// columns, so pages scraped on the sub-region and hotspot levels share one header.
+fn align_hotspot(df: DataFrame) -> PolarsResult<DataFrame> {
+    let mut df = df;
+    if df.get_column_index(HOTSPOT).is_none() {
+        df.with_column(Series::full_null(HOTSPOT, df.height(), &DataType::String))?;
+    }
+    let mut columns: Vec<_> = df
+        .get_column_names()
+        .into_iter()
+        .filter(|&c| c != HOTSPOT)
+        .collect();
+    let position = columns
+        .iter()
+        .position(|&c| c == START_MONTH)
+        .unwrap_or(columns.len());
+    columns.insert(position, HOTSPOT);
+    df.select(columns)
+}
+
+/// Orders the columns of a page like the header already written. Pages with other columns, e.g.
+/// pages scraped with `--confidence` after rows kept from a run without it, cannot share the
+/// header and fail.
+fn align_columns(
+    df: DataFrame,
+    header: &[String],
+) -> Result<DataFrame, Box<dyn Error + Send + Sync>> {
+    let columns: Vec<_> = df.get_column_names().into_iter().sorted().collect();
+    if columns != header.iter().sorted().collect::<Vec<_>>() {
+        return Err(format!(
+            "Pages have columns {} but the output has {}, so they cannot be written to one csv.",
+            df.get_column_names().join(", "),
+            header.join(", ")
+        )
+        .into());
+    }
+    Ok(df.select(header)?)
+}
+
+fn write_csv(
+    file: &str,
+    hotspot: bool,
+    confidence: Option<(f64, f64)>,
+    existing: Option<DataFrame>,
+    pages: Receiver<(usize, DataFrame)>,
+) -> WriterResult {
+    let mut file = File::create(file)?;
+    let mut header: Option<Vec<String>> = None;
+    let pages = pages.into_iter().map(|(_, df)| match confidence {
+        Some((z, max_width)) if df.height() > 0 => add_confidence(df, z, max_width),
+        _ => Ok(df),
+    });
+    for df in existing.map(Ok).into_iter().chain(pages) {
+        let mut df = df?;
+        if df.height() == 0 {
+            continue;
+        }
+        if hotspot {
+            df = align_hotspot(df)?;
+        }
+        let first = header.is_none();
+        match &header {
+            Some(columns) => df = align_columns(df, columns)?,
+            None => {
+                header = Some(
+                    df.get_column_names()
+                        .iter()
+                        .map(|c| c.to_string())
+                        .collect(),
+                )
+            }
+        }
+        CsvWriter::new(&mut file)
+            .include_header(first)
+            .finish(&mut df)?;
+    }
+    Ok(())
+}
+
+fn write_wide(file: &str, pages: Receiver<(usize, DataFrame)>) -> WriterResult {
+    let pages: Vec<_> = pages.into_iter().map(|(_, df)| df).collect();
+    let mut output = pivot_months(&concat_df_diagonal(&pages)?)?;
+    let file = File::create(file)?;
+    CsvWriter::new(&file)
+        .include_header(true)
+        .finish(&mut output)?;
+    Ok(())
+}
+
+fn write_sqlite(
+    file: &str,
+    runs: &[RunParams],
+    pages: Receiver<(usize, DataFrame)>,
+) -> WriterResult {
+    let mut writer = SqliteWriter::open(file, runs)?;
+    for (run, df) in pages {
+        // Pages that gave up or failed to parse have no rows and no checklists column.
+        if df.height() == 0 {
+            continue;
+        }
+        writer.write(run, &df)?;
+    }
+    Ok(())
+}
+
+impl PageWriter {
+    /// Starts the writer thread.
+    pub(crate) fn spawn(sink: Sink) -> Self {
+        let (sender, pages) = sync_channel(CHANNEL_CAPACITY);
+        let handle = thread::spawn(move || match sink {
+            Sink::Csv {
+                file,
+                hotspot,
+                confidence,
+                existing,
+            } => write_csv(&file, hotspot, confidence, existing, pages),
+            Sink::Wide { file } => write_wide(&file, pages),
+            Sink::Sqlite { file, runs } => write_sqlite(&file, &runs, pages),
+        });
+        Self { sender, handle }
+    }
+
+    /// Queues a page scraped by the run with index `run`. Fails if the writer has stopped.
+    pub(crate) fn send(&self, run: usize, df: DataFrame) -> PolarsResult<()> {
+        self.sender
+            .send((run, df))
+            .map_err(|_| PolarsError::ComputeError("Output writer stopped.".into()))
+    }
+
+    /// Waits for every queued page to be written and returns the first error of the writer.
+    pub(crate) fn finish(self) -> Result<(), Box<dyn Error>> {
+        drop(self.sender);
+        match self.handle.join() {
+            Ok(result) => result.map_err(|e| e as Box<dyn Error>),
+            Err(_) => Err("Output writer panicked.".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(columns: &[&str]) -> Vec<String> {
+        columns.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn align_columns_orders_pages_like_the_header() {
+        let df = df!("b" => [1], "a" => [2]).unwrap();
+        let aligned = align_columns(df, &header(&["a", "b"])).unwrap();
+        assert_eq!(aligned.get_column_names(), ["a", "b"]);
+    }
+
+    #[test]
+    fn align_columns_rejects_other_columns() {
+        let df = df!("a" => [1], "b" => [2]).unwrap();
+        assert!(align_columns(df.clone(), &header(&["a", "b", "c"])).is_err());
+        assert!(align_columns(df, &header(&["a"])).is_err());
+    }
+}
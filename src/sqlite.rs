@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use polars::prelude::{DataFrame, DataType};
+use rusqlite::{params, params_from_iter, Connection};
+
+use crate::report::string_column;
+use crate::target::scrape_params::{DateRange, ListType, LocationLevel};
+use crate::target::{
+    CHECKLISTS, COMMON_NAME, COUNTRY, END_MONTH, HOTSPOT, PERCENT, REGION, SCIENTIFIC_NAME,
+    START_MONTH, SUB_REGION,
+};
+
+static SQLITE_EXTENSIONS: &[&str] = &["db", "sqlite", "sqlite3"];
+
+static SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS locations (
+    id INTEGER PRIMARY KEY,
+    country TEXT NOT NULL,
+    region TEXT NOT NULL,
+    sub_region TEXT NOT NULL,
+    hotspot TEXT NOT NULL DEFAULT '',
+    UNIQUE (country, region, sub_region, hotspot)
+);
+CREATE TABLE IF NOT EXISTS species (
+    id INTEGER PRIMARY KEY,
+    common_name TEXT NOT NULL,
+    scientific_name TEXT NOT NULL,
+    UNIQUE (common_name, scientific_name)
+);
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    started_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    location_level TEXT NOT NULL,
+    list_type TEXT NOT NULL,
+    date_range TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS targets (
+    run_id INTEGER NOT NULL REFERENCES runs (id),
+    location_id INTEGER NOT NULL REFERENCES locations (id),
+    species_id INTEGER NOT NULL REFERENCES species (id),
+    start_month INTEGER NOT NULL,
+    end_month INTEGER NOT NULL,
+    percent REAL NOT NULL,
+    checklists INTEGER NOT NULL,
+    PRIMARY KEY (run_id, location_id, species_id, start_month, end_month)
+);
+";
+
+/// The parameters of a single scrape, recorded with every target it finds.
+#[derive(Clone)]
+pub(crate) struct RunParams {
+    location_level: String,
+    list_type: String,
+    date_range: String,
+}
+
+impl RunParams {
+    pub(crate) fn new(
+        location_level: &LocationLevel,
+        list_type: &ListType,
+        date_range: &DateRange,
+    ) -> Self {
+        Self {
+            location_level: format!("{:?}", location_level),
+            list_type: format!("{:?}", list_type),
+            date_range: format!("{:?}", date_range),
+        }
+    }
+}
+
+/// Returns true if the output file should be written as a SQLite database.
+pub(crate) fn is_sqlite(output_file: &str) -> bool {
+    Path::new(output_file)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| SQLITE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// Looks up the id of a row, inserting it first if it is not already in the table.
+fn get_or_insert(
+    conn: &Connection,
+    insert: &str,
+    select: &str,
+    values: &[String],
+) -> rusqlite::Result<i64> {
+    conn.prepare_cached(insert)?
+        .execute(params_from_iter(values))?;
+    conn.prepare_cached(select)?
+        .query_row(params_from_iter(values), |row| row.get(0))
+}
+
+/// Appends the output of one or more runs to a SQLite database, creating the schema if needed.
+///
+/// The database is normalized into four tables:
+/// - locations: One row per country, region, sub-region, and hotspot (empty for sub-region runs),
+///   including locations with no targets.
+/// - species: One row per common and scientific name.
+/// - runs: One row per run with its start time, location level, list type, and date range.
+/// - targets: The percent and checklists of each species at each location and month range in a run.
+///
+/// Targets are written page by page as they are scraped, each page in its own transaction, so a
+/// run that stops part-way keeps every page written before it and no partly written page.
+pub(crate) struct SqliteWriter {
+    conn: Connection,
+    run_ids: Vec<i64>,
+    location_ids: HashMap<[String; 4], i64>,
+    species_ids: HashMap<[String; 2], i64>,
+}
+
+impl SqliteWriter {
+    /// Opens the database and creates a row for each run.
+    pub(crate) fn open(
+        output_file: &str,
+        runs: &[RunParams],
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let conn = Connection::open(output_file)?;
+        conn.execute_batch(SCHEMA)?;
+        let run_ids = runs
+            .iter()
+            .map(|run| {
+                conn.execute(
+                    "INSERT INTO runs (location_level, list_type, date_range) VALUES (?1, ?2, ?3)",
+                    params![run.location_level, run.list_type, run.date_range],
+                )
+                .map(|_| conn.last_insert_rowid())
+            })
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(Self {
+            conn,
+            run_ids,
+            location_ids: HashMap::new(),
+            species_ids: HashMap::new(),
+        })
+    }
+
+    /// Writes and commits targets of the run with index `run`, creating their location and species
+    /// rows.
+    pub(crate) fn write(
+        &mut self,
+        run: usize,
+        df: &DataFrame,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let run_id = self.run_ids[run];
+        let common_names = string_column(df, COMMON_NAME)?;
+        let scientific_names = string_column(df, SCIENTIFIC_NAME)?;
+        let countries = string_column(df, COUNTRY)?;
+        let regions = string_column(df, REGION)?;
+        let sub_regions = string_column(df, SUB_REGION)?;
+        let hotspots = string_column(df, HOTSPOT)?;
+        let percent = df.column(PERCENT)?.cast(&DataType::Float64)?;
+        let checklists = df.column(CHECKLISTS)?.cast(&DataType::Int64)?;
+        let starts = df.column(START_MONTH)?.cast(&DataType::Int64)?;
+        let ends = df.column(END_MONTH)?.cast(&DataType::Int64)?;
+
+        let tx = self.conn.transaction()?;
+        for (i, (((p, n), s), e)) in percent
+            .f64()?
+            .into_iter()
+            .zip(checklists.i64()?)
+            .zip(starts.i64()?)
+            .zip(ends.i64()?)
+            .enumerate()
+        {
+            let location = [
+                countries[i].clone(),
+                regions[i].clone(),
+                sub_regions[i].clone(),
+                hotspots[i].clone(),
+            ];
+            let location_id = match self.location_ids.get(&location) {
+                Some(&id) => id,
+                None => {
+                    let id = get_or_insert(
+                        &tx,
+                        "INSERT OR IGNORE INTO locations (country, region, sub_region, hotspot) \
+                         VALUES (?1, ?2, ?3, ?4)",
+                        "SELECT id FROM locations \
+                         WHERE country = ?1 AND region = ?2 AND sub_region = ?3 AND hotspot = ?4",
+                        &location,
+                    )?;
+                    *self.location_ids.entry(location).or_insert(id)
+                }
+            };
+            let species = [common_names[i].clone(), scientific_names[i].clone()];
+            if species.iter().all(|s| s.is_empty()) {
+                continue;
+            }
+            let species_id = match self.species_ids.get(&species) {
+                Some(&id) => id,
+                None => {
+                    let id = get_or_insert(
+                        &tx,
+                        "INSERT OR IGNORE INTO species (common_name, scientific_name) VALUES (?1, ?2)",
+                        "SELECT id FROM species WHERE common_name = ?1 AND scientific_name = ?2",
+                        &species,
+                    )?;
+                    *self.species_ids.entry(species).or_insert(id)
+                }
+            };
+            tx.prepare_cached(
+                "INSERT OR REPLACE INTO targets \
+                 (run_id, location_id, species_id, start_month, end_month, percent, checklists) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?
+            .execute(params![
+                run_id,
+                location_id,
+                species_id,
+                s,
+                e,
+                p.unwrap_or(0.0),
+                n.unwrap_or(0)
+            ])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
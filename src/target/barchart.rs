@@ -0,0 +1,303 @@
+use crate::logging::progress_bar;
+use crate::target::engine::RUNTIME;
+use crate::target::rate_limit::RateLimiter;
+use crate::target::row::BarChartRow;
+use crate::target::scrape_params::LocationLevel;
+use crate::target::scraper::location_rows;
+use crate::target::table::{add_location_columns, barchart_table};
+use crate::target::utils::{print_hms, remove_quote};
+use crate::target::{
+    BARCHART_URL, DEFAULT_CONCURRENCY, HOME_URL, LOGIN_URL, MAX_BACKOFF, MIN_BACKOFF, WEEKS,
+};
+use indicatif::ProgressStyle;
+use polars::prelude::{DataFrame, PolarsError, PolarsResult};
+use reqwest::Client;
+use std::cmp::min;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::{block_in_place, JoinSet};
+use tracing::{debug, warn};
+
+/// Prefix of the row of weekly checklist counts in a bar chart download.
+static SAMPLE_SIZE_ROW: &str = "Sample Size:";
+
+/// Scrapes the bar chart data download of each location: the weekly frequency of every species
+/// reported there, and the number of checklists behind each week.
+pub(crate) struct BarChartScraper {
+    client: Client,
+    pub(crate) location_level: LocationLevel,
+    loc_df: DataFrame,
+    years: (u16, u16),
+    rate_limiter: Arc<RateLimiter>,
+    concurrency: usize,
+}
+
+/// Splits a bar chart species name, e.g. `Snow Goose (<em class="sci">Anser caerulescens</em>)`,
+/// into its common and scientific names. Names without a scientific name keep it empty.
+fn split_name(name: &str) -> (String, String) {
+    match name.split_once(" (<em") {
+        Some((common, rest)) => {
+            let scientific = rest
+                .split_once('>')
+                .and_then(|(_, rest)| rest.split_once("</em>"))
+                .map(|(scientific, _)| scientific.trim().to_owned())
+                .unwrap_or_default();
+            (common.trim().to_owned(), scientific)
+        }
+        None => (name.trim().to_owned(), String::new()),
+    }
+}
+
+/// Parses the tab separated values of a row, skipping empty trailing fields.
+fn parse_values(fields: &str) -> Option<Vec<f32>> {
+    fields
+        .split('\t')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .map(|f| f.parse::<f32>().ok())
+        .collect()
+}
+
+/// Reads a bar chart download into its weekly sample sizes and species rows. Frequencies are given
+/// as fractions and converted to percentages. Returns None if the download has no row of sample
+/// sizes, e.g. a partly loaded response or an error page.
+fn read_chart(text: &str) -> Option<(Vec<u32>, Vec<BarChartRow>)> {
+    let mut lines = text.lines();
+    let sample_sizes: Vec<u32> = lines
+        .by_ref()
+        .find_map(|line| line.trim_start().strip_prefix(SAMPLE_SIZE_ROW))
+        .and_then(parse_values)
+        .filter(|sizes| sizes.len() == WEEKS)?
+        .into_iter()
+        .map(|size| size.round() as u32)
+        .collect();
+
+    let rows = lines
+        .filter_map(|line| {
+            let (name, fields) = line.split_once('\t')?;
+            let frequencies = parse_values(fields).filter(|f| f.len() == WEEKS)?;
+            let (common_name, scientific_name) = split_name(name);
+            Some(BarChartRow {
+                common_name,
+                scientific_name,
+                frequencies: frequencies.into_iter().map(|f| f * 100.0).collect(),
+            })
+        })
+        .collect();
+    Some((sample_sizes, rows))
+}
+
+/// Fetches the bar chart of a location, backing off and trying again until it is read or given
+/// up on. At most one request per semaphore permit is in flight; backoffs do not hold a permit.
+async fn fetch_chart(
+    client: Client,
+    rate_limiter: Arc<RateLimiter>,
+    semaphore: Arc<Semaphore>,
+    query: Vec<(&'static str, String)>,
+) -> Option<(Vec<u32>, Vec<BarChartRow>)> {
+    let code = query[0].1.clone();
+    let mut sleep = MIN_BACKOFF;
+    loop {
+        let chart = {
+            let _permit = semaphore.acquire().await.expect("Scraper semaphore closed");
+            rate_limiter.wait().await;
+            match client.get(BARCHART_URL).query(&query).send().await {
+                Ok(response) => {
+                    let url = response.url().to_string();
+                    if url.contains(LOGIN_URL) || url.contains(HOME_URL) {
+                        debug!(url = %url, backoff = sleep, "Redirected to login, retrying");
+                        None
+                    } else {
+                        let chart = response
+                            .text()
+                            .await
+                            .ok()
+                            .and_then(|text| read_chart(&text));
+                        if chart.is_none() {
+                            debug!(url = %url, backoff = sleep, "Bar chart missing sample sizes, retrying");
+                        }
+                        chart
+                    }
+                }
+                Err(e) => {
+                    debug!(error = %e, backoff = sleep, "Request failed, retrying");
+                    None
+                }
+            }
+        };
+        if chart.is_some() {
+            return chart;
+        }
+        if sleep >= MAX_BACKOFF {
+            warn!(location = %code, backoff = sleep, "Gave up on bar chart");
+            return None;
+        }
+        tokio::time::sleep(Duration::from_secs(sleep)).await;
+        sleep = min(2 * sleep, MAX_BACKOFF);
+    }
+}
+
+impl BarChartScraper {
+    /// Scrapes the bar charts of every location in `loc_df` for observations from the first to the
+    /// last of `years`.
+    pub(crate) fn new(
+        client: Client,
+        location_level: LocationLevel,
+        loc_df: DataFrame,
+        years: (u16, u16),
+    ) -> Self {
+        Self {
+            client,
+            location_level,
+            loc_df,
+            years,
+            rate_limiter: Arc::new(RateLimiter::new(None)),
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+
+    /// Limits the number of requests sent per second across all locations.
+    pub(crate) fn with_rate_limit(mut self, requests_per_second: Option<f64>) -> Self {
+        self.rate_limiter = Arc::new(RateLimiter::new(requests_per_second));
+        self
+    }
+
+    /// Limits the number of bar charts fetched at once. Defaults to `DEFAULT_CONCURRENCY`.
+    pub(crate) fn with_concurrency(mut self, concurrency: Option<usize>) -> Self {
+        self.concurrency = concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1);
+        self
+    }
+
+    /// Builds the query of each location's bar chart download, covering the whole year.
+    fn make_queries(&self) -> Vec<Vec<(&'static str, String)>> {
+        self.loc_df
+            .column(&self.location_level.to_string())
+            .expect("Failed to get location codes")
+            .iter()
+            .map(|code| {
+                vec![
+                    ("r", remove_quote(&code.to_string())),
+                    ("bmo", "1".to_string()),
+                    ("emo", "12".to_string()),
+                    ("byr", self.years.0.to_string()),
+                    ("eyr", self.years.1.to_string()),
+                    ("fmt", "tsv".to_string()),
+                ]
+            })
+            .collect()
+    }
+
+    /// Scrapes every bar chart, passing each location's weekly frequencies to `write` as soon as
+    /// they are read. Returns the codes of the locations that were given up on. Stops early if
+    /// `write` fails.
+    pub(crate) fn stream_charts<F>(&self, write: F) -> Result<Vec<String>, PolarsError>
+    where
+        F: Fn(DataFrame) -> PolarsResult<()>,
+    {
+        let semaphore = Arc::new(Semaphore::new(self.concurrency));
+        let locations: Vec<_> = location_rows(&self.loc_df, self.location_level)
+            .into_iter()
+            .zip(self.make_queries())
+            .collect();
+
+        let start = Instant::now();
+        let style = ProgressStyle::with_template("{bar:100} {pos:>7}/{len:7} [{elapsed}] [{eta}]")
+            .expect("Failed to create progress style");
+        let progress = progress_bar(locations.len(), style);
+
+        let failed = RUNTIME.block_on(async {
+            let mut tasks = JoinSet::new();
+            let mut charts = HashMap::new();
+            for (row, query) in locations {
+                let fetch = fetch_chart(
+                    self.client.clone(),
+                    self.rate_limiter.clone(),
+                    semaphore.clone(),
+                    query.clone(),
+                );
+                let task = tasks.spawn(fetch);
+                charts.insert(task.id(), (row, query));
+            }
+            let mut failed = vec![];
+            while let Some(task) = tasks.join_next_with_id().await {
+                let id = task.as_ref().map_or_else(|e| e.id(), |(id, _)| *id);
+                let (row, query) = charts.remove(&id).expect("Unknown bar chart task");
+                let chart = match task {
+                    Ok((_, chart)) => chart,
+                    Err(e) => {
+                        warn!(location = %query[0].1, error = %e, "Bar chart task failed");
+                        None
+                    }
+                };
+                match chart {
+                    Some((sample_sizes, rows)) => {
+                        let mut df = barchart_table(&rows, &sample_sizes)?;
+                        add_location_columns(&mut df, &row)?;
+                        // Writing blocks while the writer catches up, which must not stall the
+                        // runtime.
+                        block_in_place(|| write(df))?;
+                    }
+                    None => failed.push(query[0].1.clone()),
+                }
+                progress.inc(1);
+            }
+            Ok::<_, PolarsError>(failed)
+        })?;
+        progress.finish();
+
+        print_hms(&start);
+        Ok(failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Joins `WEEKS` copies of `value` into tab separated fields, with the trailing tab eBird adds.
+    fn fields(value: &str) -> String {
+        format!("{}\t", vec![value; WEEKS].join("\t"))
+    }
+
+    #[test]
+    fn split_name_reads_common_and_scientific_names() {
+        assert_eq!(
+            split_name(r#"Snow Goose (<em class="sci">Anser caerulescens</em>)"#),
+            ("Snow Goose".to_string(), "Anser caerulescens".to_string())
+        );
+        assert_eq!(
+            split_name("duck sp. "),
+            ("duck sp.".to_string(), String::new())
+        );
+    }
+
+    #[test]
+    fn read_chart_reads_sample_sizes_and_species() {
+        let text = format!(
+            "\nFrequency of observations in the selected location(s):\n\
+             Number of taxa: 2\n\n\
+             Jan\tFeb\n\
+             {}\t{}\n\
+             Snow Goose (<em class=\"sci\">Anser caerulescens</em>)\t{}\n\
+             Partial row\t0.5\t\n",
+            SAMPLE_SIZE_ROW,
+            fields("12"),
+            fields("0.25"),
+        );
+        let (sample_sizes, rows) = read_chart(&text).expect("Failed to read chart");
+        assert_eq!(sample_sizes, vec![12; WEEKS]);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].common_name, "Snow Goose");
+        assert_eq!(rows[0].scientific_name, "Anser caerulescens");
+        assert_eq!(rows[0].frequencies, vec![25.0; WEEKS]);
+    }
+
+    #[test]
+    fn read_chart_needs_sample_sizes() {
+        assert!(read_chart("<html>Sign in</html>").is_none());
+        let partial = format!("{}\t12\t13\t\n", SAMPLE_SIZE_ROW);
+        assert!(read_chart(&partial).is_none());
+    }
+}
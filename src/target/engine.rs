@@ -0,0 +1,253 @@
+use crate::target::outcome::Outcome;
+use crate::target::rate_limit::RateLimiter;
+use crate::target::scrape_params::LocationLevel;
+use crate::target::scrape_table::scrape_table;
+use crate::target::selectors::Selectors;
+use crate::target::table::{empty_table, zero_target_table};
+use crate::target::{BASE_URL, HOME_URL, HOTSPOT, LOGIN_URL, MAX_BACKOFF, MIN_BACKOFF, REGION};
+use lazy_static::lazy_static;
+use polars::prelude::{DataFrame, PolarsResult};
+use reqwest::Client;
+use scraper::Html;
+use std::cmp::min;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::Semaphore;
+use tracing::{debug, warn};
+
+lazy_static! {
+    /// Runtime shared by every scraper. Pooled connections belong to the runtime that opened them,
+    /// so a single runtime is kept for the whole process.
+    pub(super) static ref RUNTIME: Runtime = Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to start async runtime");
+}
+
+/// The result of a single attempt at a page.
+///
+/// - Done: The page was read, or given up on, with its targets and outcome.
+/// - Retry: The page should be fetched again after backing off. Holds the backoff for the attempt
+///   after next.
+/// - Recount: The targets were parsed but did not match the species count, so the page should be
+///   fetched once more. Holds the parsed targets, kept in case the next attempt does no better.
+enum Attempt {
+    Done(DataFrame, Outcome),
+    Retry(u64),
+    Recount(DataFrame),
+}
+
+/// Fetches and parses pages asynchronously. At most `concurrency` pages are fetched or parsed at
+/// once; pages waiting out a backoff do not count towards the limit. Parsing runs on the blocking
+/// thread pool, so it is not held up by requests in flight.
+#[derive(Clone)]
+pub(super) struct Engine {
+    client: Client,
+    location_level: LocationLevel,
+    rate_limiter: Arc<RateLimiter>,
+    semaphore: Arc<Semaphore>,
+    date_query: Arc<Vec<(&'static str, String)>>,
+}
+
+impl Engine {
+    pub(super) fn new(
+        client: Client,
+        location_level: LocationLevel,
+        rate_limiter: Arc<RateLimiter>,
+        concurrency: usize,
+        date_query: Vec<(&'static str, String)>,
+    ) -> Self {
+        Self {
+            client,
+            location_level,
+            rate_limiter,
+            semaphore: Arc::new(Semaphore::new(concurrency.max(1))),
+            date_query: Arc::new(date_query),
+        }
+    }
+
+    /// Scrapes a page, backing off and trying again until it is read or given up on. A page whose
+    /// targets do not match its species count is only fetched once more; if that attempt does not
+    /// read the page either, the targets first parsed are kept with a count mismatch outcome.
+    pub(super) async fn scrape_page(
+        &self,
+        loc: &[(String, String)],
+        time: &[(String, u8)],
+    ) -> PolarsResult<(DataFrame, Outcome)> {
+        let mut sleep = MIN_BACKOFF;
+        let mut mismatched = None;
+        loop {
+            let attempt = {
+                let _permit = self
+                    .semaphore
+                    .acquire()
+                    .await
+                    .expect("Scraper semaphore closed");
+                self.attempt(loc, time, sleep, mismatched.is_none()).await?
+            };
+            match attempt {
+                Attempt::Done(_, outcome) if outcome.is_failure() && mismatched.is_some() => {
+                    let df = mismatched.expect("Checked mismatched targets");
+                    return Ok((df, Outcome::CountMismatch));
+                }
+                Attempt::Done(df, outcome) => return Ok((df, outcome)),
+                Attempt::Retry(next) => {
+                    tokio::time::sleep(Duration::from_secs(sleep)).await;
+                    sleep = next;
+                }
+                Attempt::Recount(df) => {
+                    mismatched = Some(df);
+                    tokio::time::sleep(Duration::from_secs(sleep)).await;
+                    sleep = min(2 * sleep, MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    async fn attempt(
+        &self,
+        loc: &[(String, String)],
+        time: &[(String, u8)],
+        sleep: u64,
+        recount: bool,
+    ) -> PolarsResult<Attempt> {
+        self.rate_limiter.wait().await;
+        let response = match self
+            .client
+            .get(BASE_URL)
+            .query(loc)
+            .query(time)
+            .query(self.date_query.as_slice())
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                debug!(error = %e, backoff = sleep, "Request failed, retrying");
+                return Ok(Attempt::Retry(min(2 * sleep, MAX_BACKOFF)));
+            }
+        };
+        let url = response.url().to_string();
+        if url.contains(LOGIN_URL) || url.contains(HOME_URL) {
+            debug!(url = %url, backoff = sleep, "Redirected to login, retrying");
+            return Ok(Attempt::Retry(min(2 * sleep, MAX_BACKOFF)));
+        }
+        let text = match response.text().await {
+            Ok(text) => text,
+            Err(e) => {
+                warn!(url = %url, error = %e, "Failed to read page");
+                return Ok(Attempt::Retry(min(2 * sleep, MAX_BACKOFF)));
+            }
+        };
+
+        let location_level = self.location_level;
+        let loc_code = loc[0].1.clone();
+        tokio::task::spawn_blocking(move || {
+            read_page(location_level, &text, &url, &loc_code, sleep, recount)
+        })
+        .await
+        .expect("Page parser panicked")
+    }
+}
+
+/// Parses a fetched page and decides whether it is done or should be fetched again. Targets that
+/// do not match the species count are only fetched again if `recount` is set.
+fn read_page(
+    location_level: LocationLevel,
+    text: &str,
+    url: &str,
+    loc_code: &str,
+    sleep: u64,
+    recount: bool,
+) -> PolarsResult<Attempt> {
+    let doc = Html::parse_document(text);
+    let (doc_selector, doc_format) = if location_level == LocationLevel::Hotspot {
+        (Selectors::hotspot_select(), HOTSPOT)
+    } else {
+        (Selectors::region_select(), REGION)
+    };
+
+    if doc
+        .select(doc_selector)
+        .next()
+        .and_then(|r| r.value().attr("href"))
+        .filter(|&r| r == format!("{}/{}", doc_format, loc_code))
+        .is_none()
+    {
+        return if sleep >= MAX_BACKOFF {
+            warn!(url = %url, location = %loc_code, backoff = sleep, "Gave up on page");
+            Ok(Attempt::Done(empty_table()?, Outcome::GaveUp))
+        } else {
+            debug!(url = %url, location = %loc_code, backoff = sleep, "Location missing from page, retrying");
+            Ok(Attempt::Retry(2 * sleep))
+        };
+    }
+
+    let checklists = doc
+        .select(Selectors::checklists())
+        .next()
+        .and_then(|element| element.text().next())
+        .and_then(|text| {
+            text.chars()
+                .filter(|c| c.is_numeric())
+                .collect::<String>()
+                .parse()
+                .ok()
+        })
+        .unwrap_or(0);
+
+    match doc
+        .select(Selectors::species_count())
+        .next()
+        .and_then(|count| count.text().next())
+        .and_then(|count| u32::from_str(count).ok())
+    {
+        Some(0) => Ok(Attempt::Done(
+            zero_target_table(checklists)?,
+            Outcome::ZeroTargets,
+        )),
+        Some(species_count) => match doc
+            .select(Selectors::native())
+            .next()
+            .map(|t| scrape_table(t, checklists))
+        {
+            Some(Ok(df)) if !counts_match(&doc, species_count) && recount => {
+                debug!(url = %url, location = %loc_code, backoff = sleep, "Species count mismatch, retrying once");
+                Ok(Attempt::Recount(df))
+            }
+            Some(Ok(df)) if !counts_match(&doc, species_count) => {
+                warn!(
+                    url = %url,
+                    location = %loc_code,
+                    species_count,
+                    rows = doc.select(Selectors::rows()).count(),
+                    "Parsed rows do not match species count"
+                );
+                Ok(Attempt::Done(df, Outcome::CountMismatch))
+            }
+            Some(Ok(df)) => Ok(Attempt::Done(df, Outcome::Ok)),
+            Some(Err(e)) => {
+                warn!(url = %url, location = %loc_code, error = %e, "Failed to parse targets");
+                Ok(Attempt::Done(empty_table()?, Outcome::ParseError))
+            }
+            None => {
+                warn!(url = %url, location = %loc_code, "Targets table missing");
+                Ok(Attempt::Done(empty_table()?, Outcome::ParseError))
+            }
+        },
+        None => {
+            debug!(url = %url, location = %loc_code, backoff = sleep, "Species count missing, retrying");
+            Ok(Attempt::Retry(min(2 * sleep, MAX_BACKOFF)))
+        }
+    }
+}
+
+/// Checks the species count in the page heading against the number of species rows on the page.
+/// The heading counts the targets in every section, native and exotic, so rows are counted across
+/// the whole page rather than only the native section that is parsed. A mismatch is a sign of a
+/// partly loaded page or of selectors that no longer match the page.
+fn counts_match(doc: &Html, species_count: u32) -> bool {
+    doc.select(Selectors::rows()).count() == species_count as usize
+}
@@ -0,0 +1,45 @@
+mod barchart;
+mod engine;
+mod rate_limit;
+pub(crate) mod outcome;
+pub mod row;
+pub mod scrape_params;
+mod scrape_table;
+mod scraper;
+pub(crate) mod selectors;
+mod table;
+mod utils;
+
+pub(crate) use barchart::BarChartScraper;
+pub use scraper::Scraper;
+pub(crate) use scraper::required_columns;
+pub(crate) use table::{month_label, pivot_months};
+pub use utils::{format_hms, print_hms};
+static BARCHART_URL: &str = "https://ebird.org/barchartData";
+static BASE_URL: &str = "https://ebird.org/targets";
+pub(crate) static CHECKLISTS: &str = "checklists";
+static CODE: &str = "code";
+pub(crate) static COMMON_NAME: &str = "common name";
+pub(crate) static COUNTRY: &str = "country";
+/// Number of pages fetched or parsed at once when no concurrency is given.
+static DEFAULT_CONCURRENCY: usize = 16;
+pub(crate) static END_MONTH: &str = "end month";
+static HOME_URL: &str = "https://ebird.org/home";
+pub(crate) static HOTSPOT: &str = "hotspot";
+static HOTSPOT_COLUMNS: &[&str] = &["country", "region", "sub_region", "hotspot"];
+static LOGIN_URL: &str = "https://secure.birds.cornell.edu/cassso/login";
+static MAX_BACKOFF: u64 = 100;
+static MIN_BACKOFF: u64 = 5;
+static OUTCOME: &str = "outcome";
+pub(crate) static PERCENT: &str = "percent";
+pub(crate) static REGION: &str = "region";
+static REGION_COLUMNS: &[&str] = &["country", "region", "sub_region"];
+pub(crate) static START_MONTH: &str = "start month";
+pub(crate) static SUB_REGION: &str = "sub_region";
+pub(crate) static SAMPLE_SIZE: &str = "sample size";
+pub(crate) static SCIENTIFIC_NAME: &str = "scientific name";
+/// Typical time to fetch and parse one targets page, used to estimate run times.
+static SECONDS_PER_PAGE: f64 = 2.0;
+pub(crate) static WEEK: &str = "week";
+/// Number of weekly columns in a bar chart, four for each month.
+static WEEKS: usize = 48;
@@ -0,0 +1,219 @@
+use crate::error::MagpieError;
+use crate::loc::load_data;
+use crate::target::row::LocationRow;
+use crate::target::table::append_rows;
+use crate::target::{CODE, COUNTRY, END_MONTH, HOTSPOT, OUTCOME, REGION, START_MONTH, SUB_REGION};
+use polars::prelude::*;
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The result of scraping a single page.
+///
+/// - Ok: The page was read and its table of targets parsed.
+/// - ZeroTargets: The page was read and has no targets.
+/// - GaveUp: The page never showed the requested location, even after backing off to `MAX_BACKOFF`.
+/// - ParseError: The page reported targets, but the table of targets could not be parsed, or
+///   scraping the page failed unexpectedly.
+/// - CountMismatch: The table was parsed, but its number of rows did not match the species count
+///   in the page heading, also when the page was fetched once more. The parsed targets are kept.
+#[derive(Display, EnumString, Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Outcome {
+    #[strum(serialize = "ok")]
+    Ok,
+    #[strum(serialize = "zero_targets")]
+    ZeroTargets,
+    #[strum(serialize = "gave_up")]
+    GaveUp,
+    #[strum(serialize = "parse_error")]
+    ParseError,
+    #[strum(serialize = "count_mismatch")]
+    CountMismatch,
+}
+
+impl Outcome {
+    /// Returns true if the page should be scraped again.
+    pub(crate) fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            Outcome::GaveUp | Outcome::ParseError | Outcome::CountMismatch
+        )
+    }
+}
+
+/// A page request, identified by its location code and month range. Used to select the pages
+/// to scrape again.
+pub(crate) type PageKey = (String, u8, u8);
+
+/// The outcome of one page request, with the location and months it was for.
+pub(crate) struct PageOutcome {
+    pub(super) code: String,
+    pub(super) row: LocationRow,
+    pub(super) start_month: u8,
+    pub(super) end_month: u8,
+    pub(super) outcome: Outcome,
+}
+
+/// Collects page outcomes into a table with one row per page request.
+pub(super) fn outcome_table(outcomes: &[PageOutcome]) -> Result<DataFrame, PolarsError> {
+    let mut df = DataFrame::new(vec![
+        Series::new(
+            CODE,
+            outcomes.iter().map(|o| o.code.as_str()).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            COUNTRY,
+            outcomes.iter().map(|o| o.row.country()).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            REGION,
+            outcomes.iter().map(|o| o.row.region()).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            SUB_REGION,
+            outcomes
+                .iter()
+                .map(|o| o.row.sub_region())
+                .collect::<Vec<_>>(),
+        ),
+    ])?;
+    if outcomes.iter().any(|o| o.row.hotspot().is_some()) {
+        df.with_column(Series::new(
+            HOTSPOT,
+            outcomes.iter().map(|o| o.row.hotspot()).collect::<Vec<_>>(),
+        ))?;
+    }
+    df.with_column(Series::new(
+        START_MONTH,
+        outcomes
+            .iter()
+            .map(|o| o.start_month as u32)
+            .collect::<Vec<_>>(),
+    ))?;
+    df.with_column(Series::new(
+        END_MONTH,
+        outcomes
+            .iter()
+            .map(|o| o.end_month as u32)
+            .collect::<Vec<_>>(),
+    ))?;
+    df.with_column(Series::new(
+        OUTCOME,
+        outcomes
+            .iter()
+            .map(|o| o.outcome.to_string())
+            .collect::<Vec<_>>(),
+    ))?;
+    Ok(df)
+}
+
+/// Returns the code and months of each row of an outcome table, with its outcome.
+fn page_outcomes(df: &DataFrame) -> Result<Vec<(PageKey, Outcome)>, PolarsError> {
+    let codes = df.column(CODE)?.cast(&DataType::String)?;
+    let starts = df.column(START_MONTH)?.cast(&DataType::UInt32)?;
+    let ends = df.column(END_MONTH)?.cast(&DataType::UInt32)?;
+    let outcomes = df.column(OUTCOME)?.cast(&DataType::String)?;
+    codes
+        .str()?
+        .into_iter()
+        .zip(starts.u32()?)
+        .zip(ends.u32()?)
+        .zip(outcomes.str()?)
+        .map(|(((code, start), end), outcome)| {
+            let key = (
+                code.unwrap_or_default().to_string(),
+                start.unwrap_or_default() as u8,
+                end.unwrap_or_default() as u8,
+            );
+            let outcome = outcome
+                .and_then(|o| Outcome::from_str(o).ok())
+                .ok_or_else(|| {
+                    PolarsError::ComputeError(
+                        format!("Invalid outcome {}", outcome.unwrap_or_default()).into(),
+                    )
+                })?;
+            Ok((key, outcome))
+        })
+        .collect()
+}
+
+/// Returns the pages of an outcome table that failed and should be scraped again.
+pub(crate) fn failed_pages(df: &DataFrame) -> Result<HashSet<PageKey>, PolarsError> {
+    Ok(page_outcomes(df)?
+        .into_iter()
+        .filter(|(_, outcome)| outcome.is_failure())
+        .map(|(key, _)| key)
+        .collect())
+}
+
+/// Replaces the outcomes of pages that were scraped again with their new outcomes.
+pub(crate) fn merge_outcomes(old: &DataFrame, new: &DataFrame) -> Result<DataFrame, PolarsError> {
+    let retried: HashSet<_> = page_outcomes(new)?.into_iter().map(|(k, _)| k).collect();
+    let keep: BooleanChunked = page_outcomes(old)?
+        .iter()
+        .map(|(key, _)| !retried.contains(key))
+        .collect();
+    append_rows(&old.filter(&keep)?, new)
+}
+
+/// Returns the values of the location and month columns of each row, as strings.
+fn location_keys(df: &DataFrame) -> Result<Vec<Vec<Option<String>>>, PolarsError> {
+    let columns = [COUNTRY, REGION, SUB_REGION, HOTSPOT, START_MONTH, END_MONTH]
+        .into_iter()
+        .map(|name| match df.column(name) {
+            Ok(column) => column.cast(&DataType::String),
+            Err(_) => Ok(Series::full_null(name, df.height(), &DataType::String)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut iters = columns
+        .iter()
+        .map(|c| c.str().map(|ca| ca.into_iter()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((0..df.height())
+        .map(|_| {
+            iters
+                .iter_mut()
+                .map(|iter| iter.next().flatten().map(ToString::to_string))
+                .collect()
+        })
+        .collect())
+}
+
+/// Removes the rows of an output table that were scraped from the given pages, so that they can
+/// be replaced by the rows of a retry. Pages are matched to rows through the locations and months
+/// recorded in the outcome table.
+pub(crate) fn drop_pages(
+    output: &DataFrame,
+    outcomes: &DataFrame,
+    pages: &HashSet<PageKey>,
+) -> Result<DataFrame, PolarsError> {
+    let dropped: HashSet<_> = page_outcomes(outcomes)?
+        .into_iter()
+        .zip(location_keys(outcomes)?)
+        .filter(|((key, _), _)| pages.contains(key))
+        .map(|(_, location)| location)
+        .collect();
+    let keep: BooleanChunked = location_keys(output)?
+        .iter()
+        .map(|location| !dropped.contains(location))
+        .collect();
+    output.filter(&keep)
+}
+
+/// Names the outcome file written next to an output file, e.g. `out.failures.csv` for `out.csv`.
+pub(crate) fn failures_file(output_file: &str) -> String {
+    Path::new(output_file)
+        .with_extension("failures.csv")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Loads the outcome file written next to an output file, if there is one.
+pub(crate) fn load_failures(output_file: &str) -> Result<Option<DataFrame>, MagpieError> {
+    let file = failures_file(output_file);
+    if Path::new(&file).exists() {
+        load_data(&file).map(Some)
+    } else {
+        Ok(None)
+    }
+}
@@ -0,0 +1,40 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Spaces requests evenly so that no more than a fixed number are sent per second across all
+/// pages. Without a rate, requests are sent as fast as the concurrency limit allows.
+pub(crate) struct RateLimiter {
+    interval: Option<Duration>,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(requests_per_second: Option<f64>) -> Self {
+        Self {
+            interval: requests_per_second
+                .filter(|&r| r > 0.0)
+                .map(|r| Duration::from_secs_f64(1.0 / r)),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until the next request slot is available.
+    pub(crate) async fn wait(&self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        let now = Instant::now();
+        let slot = {
+            let mut next = self.next.lock().expect("Rate limiter lock poisoned");
+            let slot = (*next).max(now);
+            *next = slot + interval;
+            slot
+        };
+        tokio::time::sleep(slot - now).await;
+    }
+
+    /// Returns the shortest time in which the rate limit allows `requests` requests.
+    pub(crate) fn min_duration(&self, requests: usize) -> Option<Duration> {
+        self.interval.map(|interval| interval * requests as u32)
+    }
+}
@@ -0,0 +1,289 @@
+use crate::logging::progress_bar;
+use crate::target::engine::{Engine, RUNTIME};
+use crate::target::outcome::{outcome_table, Outcome, PageKey, PageOutcome};
+use crate::target::rate_limit::RateLimiter;
+use crate::target::row::LocationRow;
+use crate::target::scrape_params::{DateRange, ListType, LocationLevel};
+use crate::target::table::{add_columns, empty_table};
+use crate::target::utils::{print_hms, remove_quote};
+use crate::target::{
+    BASE_URL, DEFAULT_CONCURRENCY, HOTSPOT_COLUMNS, REGION_COLUMNS, SECONDS_PER_PAGE,
+};
+use indicatif::ProgressStyle;
+use itertools::Itertools;
+use polars::prelude::{DataFrame, PolarsError, PolarsResult};
+use reqwest::Client;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::{block_in_place, JoinSet};
+use tracing::warn;
+
+pub struct Scraper {
+    client: Client,
+    pub(super) date_range: DateRange,
+    pub(crate) location_level: LocationLevel,
+    list_type: ListType,
+    loc_df: DataFrame,
+    time_range: Vec<(u8, u8)>,
+    rate_limiter: Arc<RateLimiter>,
+    concurrency: usize,
+    retry_pages: Option<HashSet<PageKey>>,
+}
+
+/// The requests a run would send, without sending them.
+pub struct DryRun {
+    pub requests: usize,
+    pub sample_urls: Vec<String>,
+    pub eta: Duration,
+}
+
+type Payload = ((LocationRow, Vec<(String, String)>), Vec<(String, u8)>);
+
+impl Scraper {
+    pub(crate) fn new(
+        client: Client,
+        date_range: DateRange,
+        list_level: LocationLevel,
+        list_type: ListType,
+        loc_df: DataFrame,
+        time_range: Vec<(u8, u8)>,
+    ) -> Self {
+        Self {
+            client,
+            date_range,
+            location_level: list_level,
+            list_type,
+            loc_df,
+            time_range,
+            rate_limiter: Arc::new(RateLimiter::new(None)),
+            concurrency: DEFAULT_CONCURRENCY,
+            retry_pages: None,
+        }
+    }
+
+    /// Limits the number of requests sent per second across all pages.
+    pub(crate) fn with_rate_limit(mut self, requests_per_second: Option<f64>) -> Self {
+        self.rate_limiter = Arc::new(RateLimiter::new(requests_per_second));
+        self
+    }
+
+    /// Limits the number of pages fetched or parsed at once. Defaults to `DEFAULT_CONCURRENCY`.
+    pub(crate) fn with_concurrency(mut self, concurrency: Option<usize>) -> Self {
+        self.concurrency = concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1);
+        self
+    }
+
+    /// Restricts the scrape to the given pages, e.g. the failures of an earlier run.
+    pub(crate) fn with_retry_pages(mut self, retry_pages: Option<HashSet<PageKey>>) -> Self {
+        self.retry_pages = retry_pages;
+        self
+    }
+
+    fn make_loc_vec(&self) -> Vec<LocationRow> {
+        location_rows(&self.loc_df, self.location_level)
+    }
+
+    fn make_loc_payload(&self) -> Vec<Vec<(String, String)>> {
+        let location_level_code = self.location_level.to_string();
+        let columns = if self.list_type == ListType::Global {
+            vec![location_level_code]
+        } else {
+            vec![location_level_code, self.list_type.to_string()]
+        };
+        let mut col_iters = self
+            .loc_df
+            .columns(columns)
+            .expect("Failed to get columns for payload")
+            .iter()
+            .map(|&s| s.iter())
+            .collect::<Vec<_>>();
+
+        let mut loc_payload: Vec<Vec<(String, String)>> = (0..self.loc_df.shape().0)
+            .map(|_| {
+                col_iters
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(i, iter)| {
+                        let value = iter.next().unwrap().to_string();
+                        (format!("r{}", i + 1), remove_quote(&value))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        if self.list_type == ListType::Global {
+            loc_payload.iter_mut().for_each(|payload| {
+                payload.push(("r2".to_string(), "world".to_string()));
+            });
+        }
+        loc_payload
+    }
+
+    fn make_time_payload(&self) -> Vec<Vec<(String, u8)>> {
+        self.time_range
+            .iter()
+            .map(|&(s, e)| vec![("bmo".to_string(), s), ("emo".to_string(), e)])
+            .collect()
+    }
+
+    fn make_date_query(&self) -> Vec<(&'static str, String)> {
+        vec![("t2", self.date_range.to_string())]
+    }
+
+    /// Pairs each location with each month range. Every payload is one page request.
+    /// When retrying, only the payloads of the pages to retry are kept.
+    fn make_payloads(&self) -> Vec<Payload> {
+        self.make_loc_vec()
+            .into_iter()
+            .zip(self.make_loc_payload())
+            .cartesian_product(self.make_time_payload())
+            .filter(|((_, loc), time)| {
+                self.retry_pages
+                    .as_ref()
+                    .map_or(true, |pages| pages.contains(&page_key(loc, time)))
+            })
+            .collect()
+    }
+
+    /// Builds the requests `stream_pages` would send and estimates how long they would take, without
+    /// sending them. The estimate is the slower of the rate limit and the typical page time spread
+    /// over the pages fetched at once.
+    pub fn dry_run(&self, samples: usize) -> Result<DryRun, reqwest::Error> {
+        let date_query = self.make_date_query();
+        let payloads = self.make_payloads();
+        let sample_urls = payloads
+            .iter()
+            .take(samples)
+            .map(|((_, loc), time)| {
+                self.client
+                    .get(BASE_URL)
+                    .query(loc)
+                    .query(time)
+                    .query(&date_query)
+                    .build()
+                    .map(|request| request.url().to_string())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let estimate = Duration::from_secs_f64(
+            payloads.len() as f64 * SECONDS_PER_PAGE / self.concurrency as f64,
+        );
+        let eta = self
+            .rate_limiter
+            .min_duration(payloads.len())
+            .map_or(estimate, |limit| limit.max(estimate));
+        Ok(DryRun {
+            requests: payloads.len(),
+            sample_urls,
+            eta,
+        })
+    }
+
+    /// Scrapes every page, passing each page's targets to `write` as soon as the page is done.
+    /// Returns a table of the outcome of each page. Stops early if `write` fails.
+    pub fn stream_pages<F>(&self, write: F) -> Result<DataFrame, PolarsError>
+    where
+        F: Fn(DataFrame) -> PolarsResult<()>,
+    {
+        let engine = Engine::new(
+            self.client.clone(),
+            self.location_level,
+            self.rate_limiter.clone(),
+            self.concurrency,
+            self.make_date_query(),
+        );
+        let payloads = self.make_payloads();
+
+        let start = Instant::now();
+        let style = ProgressStyle::with_template("{bar:100} {pos:>7}/{len:7} [{elapsed}] [{eta}]")
+            .expect("Failed to create progress style");
+        let progress = progress_bar(payloads.len(), style);
+
+        let outcomes = RUNTIME.block_on(async {
+            let mut tasks = JoinSet::new();
+            let mut pages = HashMap::new();
+            for ((row, loc), time) in payloads {
+                let engine = engine.clone();
+                let (task_loc, task_time) = (loc.clone(), time.clone());
+                let task =
+                    tasks.spawn(async move { engine.scrape_page(&task_loc, &task_time).await });
+                pages.insert(task.id(), (row, loc, time));
+            }
+            let mut outcomes = Vec::with_capacity(tasks.len());
+            while let Some(task) = tasks.join_next_with_id().await {
+                let id = task.as_ref().map_or_else(|e| e.id(), |(id, _)| *id);
+                let (row, loc, time) = pages.remove(&id).expect("Unknown page task");
+                let (mut df, outcome) = match task {
+                    Ok((_, page)) => page?,
+                    Err(e) => {
+                        warn!(location = %loc[0].1, error = %e, "Page task failed");
+                        (empty_table()?, Outcome::ParseError)
+                    }
+                };
+                add_columns(&mut df, &row, &time)?;
+                // Writing blocks while the writer catches up, which must not stall the runtime.
+                block_in_place(|| write(df))?;
+                progress.inc(1);
+                outcomes.push(PageOutcome {
+                    code: page_key(&loc, &time).0,
+                    row,
+                    start_month: time[0].1,
+                    end_month: time[1].1,
+                    outcome,
+                });
+            }
+            Ok::<_, PolarsError>(outcomes)
+        })?;
+        progress.finish();
+
+        print_hms(&start);
+        outcome_table(&outcomes)
+    }
+}
+
+/// Returns the columns a scrape reads from a location table: the location names, the code of each
+/// location, and, unless the list is global, the code of the list's parent location.
+pub(crate) fn required_columns(
+    location_level: LocationLevel,
+    list_type: Option<&ListType>,
+) -> Vec<String> {
+    let loc_vec = if location_level == LocationLevel::Hotspot {
+        HOTSPOT_COLUMNS
+    } else {
+        REGION_COLUMNS
+    };
+    loc_vec
+        .iter()
+        .map(|c| c.to_string())
+        .chain([location_level.to_string()])
+        .chain(
+            list_type
+                .filter(|&t| *t != ListType::Global)
+                .map(|t| t.to_string()),
+        )
+        .unique()
+        .collect()
+}
+
+/// Reads the location columns of each row of a location table, with the hotspot on the hotspot level.
+pub(super) fn location_rows(loc_df: &DataFrame, location_level: LocationLevel) -> Vec<LocationRow> {
+    let loc_vec = if location_level == LocationLevel::Hotspot {
+        HOTSPOT_COLUMNS
+    } else {
+        REGION_COLUMNS
+    };
+    let mut loc = loc_df
+        .columns(loc_vec)
+        .expect("Failed to get location columns")
+        .iter()
+        .map(|&s| s.iter())
+        .collect::<Vec<_>>();
+    (0..loc_df.shape().0)
+        .map(|_| LocationRow::new(&mut loc))
+        .collect()
+}
+
+/// Identifies a page request by its location code and month range.
+fn page_key(loc: &[(String, String)], time: &[(String, u8)]) -> PageKey {
+    (loc[0].1.clone(), time[0].1, time[1].1)
+}
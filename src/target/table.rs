@@ -0,0 +1,307 @@
+use crate::target::row::{BarChartRow, LocationRow, TargetRow};
+use crate::target::{
+    CHECKLISTS, COMMON_NAME, COUNTRY, END_MONTH, HOTSPOT, PERCENT, REGION, SAMPLE_SIZE,
+    SCIENTIFIC_NAME, START_MONTH, SUB_REGION, WEEK,
+};
+use itertools::Itertools;
+use polars::prelude::pivot::pivot_stable;
+use polars::prelude::*;
+use std::collections::HashSet;
+
+static MONTHS: &str = "months";
+
+/// Adds columns that are constant for each scraped page. These columns are the location information:
+/// sub-region, region, country, hotspot (if applicable), and the start and end months.
+/// Each column repeats a single value, so string values are stored once per page rather than once per row.
+pub(super) fn add_columns(
+    df: &mut DataFrame,
+    row: &LocationRow,
+    time: &[(String, u8)],
+) -> Result<(), PolarsError> {
+    add_location_columns(df, row)?;
+
+    let size = df.height();
+    df.with_column(UInt32Chunked::full(START_MONTH, time[0].1 as u32, size).into_series())?;
+    df.with_column(UInt32Chunked::full(END_MONTH, time[1].1 as u32, size).into_series())?;
+
+    Ok(())
+}
+
+/// Adds the location columns of a page: sub-region, region, country, and hotspot (if applicable).
+pub(super) fn add_location_columns(
+    df: &mut DataFrame,
+    row: &LocationRow,
+) -> Result<(), PolarsError> {
+    let size = df.height();
+    let constant_columns = [
+        (SUB_REGION, row.sub_region()),
+        (REGION, row.region()),
+        (COUNTRY, row.country()),
+    ];
+
+    for (name, value) in constant_columns {
+        df.with_column(StringChunked::full(name, value, size).into_series())?;
+    }
+
+    if let Some(hotspot) = row.hotspot() {
+        df.with_column(StringChunked::full(HOTSPOT, hotspot, size).into_series())?;
+    }
+
+    Ok(())
+}
+
+/// Converts the species rows of a page into a table, along with the number of checklists behind them.
+pub(super) fn target_table(rows: &[TargetRow], checklists: i32) -> Result<DataFrame, PolarsError> {
+    DataFrame::new(vec![
+        Series::new(
+            COMMON_NAME,
+            rows.iter()
+                .map(|r| r.common_name.as_str())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            SCIENTIFIC_NAME,
+            rows.iter()
+                .map(|r| r.scientific_name.as_str())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(PERCENT, rows.iter().map(|r| r.percent).collect::<Vec<_>>()),
+        Int32Chunked::full(CHECKLISTS, checklists, rows.len()).into_series(),
+    ])
+}
+
+/// Converts the species rows of a bar chart into a long table with one row per species and week,
+/// along with the number of checklists behind each week. Weeks are numbered 1 to 48, four to a month.
+pub(super) fn barchart_table(
+    rows: &[BarChartRow],
+    sample_sizes: &[u32],
+) -> Result<DataFrame, PolarsError> {
+    let weeks = sample_sizes.len();
+    let repeat = |name: fn(&BarChartRow) -> &str| {
+        rows.iter()
+            .flat_map(|r| std::iter::repeat(name(r)).take(weeks))
+            .collect::<Vec<_>>()
+    };
+    DataFrame::new(vec![
+        Series::new(COMMON_NAME, repeat(|r| r.common_name.as_str())),
+        Series::new(SCIENTIFIC_NAME, repeat(|r| r.scientific_name.as_str())),
+        Series::new(
+            WEEK,
+            rows.iter()
+                .flat_map(|_| 1..=weeks as u32)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            PERCENT,
+            rows.iter()
+                .flat_map(|r| r.frequencies.iter().copied())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            SAMPLE_SIZE,
+            rows.iter()
+                .flat_map(|_| sample_sizes.iter().copied())
+                .collect::<Vec<_>>(),
+        ),
+    ])
+}
+
+/// Appends the rows of `new` to `old`, casting shared columns to the types of `new`. Tables read
+/// back from CSV may have inferred different types than freshly scraped ones.
+pub(crate) fn append_rows(old: &DataFrame, new: &DataFrame) -> Result<DataFrame, PolarsError> {
+    let mut old = old.clone();
+    for column in new.get_columns() {
+        if let Ok(old_column) = old.column(column.name()) {
+            let cast = old_column.cast(column.dtype())?;
+            old.with_column(cast)?;
+        }
+    }
+    polars::functions::concat_df_diagonal(&[old, new.clone()])
+}
+
+/// In cases where there is no data to return, returns an empty table.
+pub(super) fn empty_table() -> Result<DataFrame, PolarsError> {
+    DataFrame::new(vec![
+        Series::new(COMMON_NAME, Vec::<String>::new()),
+        Series::new(SCIENTIFIC_NAME, Vec::<String>::new()),
+        Series::new(PERCENT, Vec::<f32>::new()),
+    ])
+}
+
+/// For locations with no targets, returns a single row with no species, so the location still appears
+/// in the output along with the number of checklists behind it.
+pub(super) fn zero_target_table(checklists: i32) -> Result<DataFrame, PolarsError> {
+    DataFrame::new(vec![
+        Series::new(COMMON_NAME, [None::<&str>]),
+        Series::new(SCIENTIFIC_NAME, [None::<&str>]),
+        Series::new(PERCENT, [None::<f32>]),
+        Series::new(CHECKLISTS, [checklists]),
+    ])
+}
+
+/// Labels a month range by its month number, or by its start and end months if it spans more than one month.
+pub(crate) fn month_label(start: u32, end: u32) -> String {
+    if start == end {
+        start.to_string()
+    } else {
+        format!("{}-{}", start, end)
+    }
+}
+
+/// Returns the values of the location columns of each row, to match rows of the same location.
+fn location_keys(df: &DataFrame, columns: &[&str]) -> PolarsResult<Vec<Vec<Option<String>>>> {
+    let mut keys = vec![vec![]; df.height()];
+    for &column in columns {
+        let values = df.column(column)?.cast(&DataType::String)?;
+        for (key, value) in keys.iter_mut().zip(values.str()?) {
+            key.push(value.map(ToString::to_string));
+        }
+    }
+    Ok(keys)
+}
+
+/// Pivots a long output table, one row per species, location, and month range, into a wide table with
+/// one row per species and location and one frequency column per month range.
+/// Month range columns are in calendar order. Species not reported in a month range that was scraped
+/// for their location have frequency zero, month ranges that were not scraped, e.g. pages that gave up,
+/// are left empty. Locations with no targets keep a single row without species and with empty months.
+pub(crate) fn pivot_months(df: &DataFrame) -> Result<DataFrame, PolarsError> {
+    let starts = df.column(START_MONTH)?.cast(&DataType::UInt32)?;
+    let ends = df.column(END_MONTH)?.cast(&DataType::UInt32)?;
+    let month_ranges: Vec<_> = starts
+        .u32()?
+        .into_no_null_iter()
+        .zip(ends.u32()?.into_no_null_iter())
+        .map(|(s, e)| month_label(s, e))
+        .collect();
+    let month_columns: Vec<_> = starts
+        .u32()?
+        .into_no_null_iter()
+        .zip(ends.u32()?.into_no_null_iter())
+        .unique()
+        .sorted()
+        .map(|(s, e)| month_label(s, e))
+        .collect();
+
+    let mut locations = vec![COUNTRY, REGION, SUB_REGION];
+    if df.get_column_index(HOTSPOT).is_some() {
+        locations.push(HOTSPOT);
+    }
+    let keys = location_keys(df, &locations)?;
+    let has_species: Vec<_> = df
+        .column(COMMON_NAME)?
+        .is_not_null()
+        .into_no_null_iter()
+        .collect();
+    let scraped: HashSet<_> = keys.iter().zip(&month_ranges).collect();
+    let with_targets: HashSet<_> = keys
+        .iter()
+        .zip(&has_species)
+        .filter(|(_, &species)| species)
+        .map(|(key, _)| key)
+        .collect();
+
+    // Rows without a species are only kept for locations without targets in any month range.
+    let keep: BooleanChunked = keys
+        .iter()
+        .zip(&has_species)
+        .map(|(key, &species)| species || !with_targets.contains(key))
+        .collect();
+    let mut long = df.filter(&keep)?;
+    long.with_column(Series::new(
+        MONTHS,
+        month_ranges
+            .iter()
+            .zip(keep.into_no_null_iter())
+            .filter(|(_, keep)| *keep)
+            .map(|(month, _)| month.as_str())
+            .collect::<Vec<_>>(),
+    ))?;
+
+    let mut index = vec![COMMON_NAME, SCIENTIFIC_NAME];
+    index.extend(&locations);
+    let mut wide = pivot_stable(
+        &long,
+        [MONTHS],
+        Some(index.clone()),
+        Some([PERCENT]),
+        false,
+        Some(col(PERCENT).first()),
+        None,
+    )?;
+
+    let wide_keys = location_keys(&wide, &locations)?;
+    let wide_species: Vec<_> = wide
+        .column(COMMON_NAME)?
+        .is_not_null()
+        .into_no_null_iter()
+        .collect();
+    for month in &month_columns {
+        let percent = match wide.get_column_index(month) {
+            Some(_) => wide.column(month)?.cast(&DataType::Float32)?,
+            None => Series::full_null(month, wide.height(), &DataType::Float32),
+        };
+        let filled: Float32Chunked = percent
+            .f32()?
+            .into_iter()
+            .zip(&wide_keys)
+            .zip(&wide_species)
+            .map(|((p, key), &species)| match p {
+                None if species && scraped.contains(&(key, month)) => Some(0.0),
+                p => p,
+            })
+            .collect();
+        wide.with_column(filled.with_name(month).into_series())?;
+    }
+
+    let columns = index
+        .into_iter()
+        .chain(month_columns.iter().map(String::as_str))
+        .collect::<Vec<_>>();
+    wide.select(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pivot_months_keeps_zero_target_locations_and_unscraped_months_empty() {
+        let df = df!(
+            COMMON_NAME => [Some("Snow Goose"), None, None, None, Some("Snow Goose")],
+            SCIENTIFIC_NAME => [Some("Anser caerulescens"), None, None, None, Some("Anser caerulescens")],
+            PERCENT => [Some(10.0f32), None, None, None, Some(20.0)],
+            CHECKLISTS => [5, 4, 3, 3, 6],
+            COUNTRY => ["US"; 5],
+            REGION => ["New York"; 5],
+            SUB_REGION => ["Albany", "Albany", "Bronx", "Bronx", "Cayuga"],
+            START_MONTH => [1u32, 2, 1, 2, 1],
+            END_MONTH => [1u32, 2, 1, 2, 1],
+        )
+        .unwrap();
+
+        let wide = pivot_months(&df).unwrap();
+        let wide = wide.sort([SUB_REGION], Default::default()).unwrap();
+        let month = |m: &str| {
+            wide.column(m)
+                .unwrap()
+                .f32()
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+        // Albany had no Snow Goose in February, the Bronx had no targets, and Cayuga's February
+        // page was never read.
+        assert_eq!(
+            wide.column(COMMON_NAME)
+                .unwrap()
+                .str()
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![Some("Snow Goose"), None, Some("Snow Goose")]
+        );
+        assert_eq!(month("1"), vec![Some(10.0), None, Some(20.0)]);
+        assert_eq!(month("2"), vec![Some(0.0), None, None]);
+    }
+}
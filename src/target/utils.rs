@@ -0,0 +1,29 @@
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Removes first and last char from a string. Used when quotes are inproperly included in strings.
+pub(super) fn remove_quote(value: &str) -> String {
+    let mut chars = value.chars();
+    chars.next();
+    chars.next_back();
+    chars.as_str().to_string()
+}
+
+/// Formats a duration as hours, minutes, seconds, and milliseconds.
+pub fn format_hms(duration: &Duration) -> String {
+    let millis = duration.as_millis();
+    let seconds = millis / 1000;
+    let (hour, minute, second) = (seconds / 3600, (seconds % 3600) / 60, seconds % 60);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        hour,
+        minute,
+        second,
+        millis % 1000
+    )
+}
+
+/// Logs the run time of a procedure in human readable format.
+pub fn print_hms(start: &Instant) {
+    info!("Elapsed time: {}", format_hms(&start.elapsed()));
+}